@@ -0,0 +1,88 @@
+/// Command line arguments, parsed by hand since there are only a few of them.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// The page to fetch from Notion, as an id or URL.
+    pub page: Option<String>,
+    /// A saved block children response to convert instead, `-` for stdin.
+    pub file: Option<String>,
+    /// Save every API response into this directory.
+    pub record: Option<String>,
+    /// Serve API responses from this directory instead of Notion.
+    pub replay: Option<String>,
+    pub api_url: Option<String>,
+    pub notion_version: Option<String>,
+    pub max_attempts: Option<u32>,
+    pub expand_toggles: bool,
+    /// Leave equations as LaTeX rather than converting them to MathML.
+    pub raw_math: bool,
+    /// Download files into this directory.
+    pub assets: Option<String>,
+    /// Link downloaded files relative to this instead of the directory.
+    pub assets_url: Option<String>,
+    /// Fetch bookmarked pages for their titles.
+    pub link_titles: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut out = Args::default();
+
+        while let Some(arg) = args.next() {
+            // allow `--flag=value` as well as `--flag value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => out.help = true,
+                "-f" | "--file" => out.file = Some(value()?),
+                "--expand-toggles" => out.expand_toggles = true,
+                "--raw-math" => out.raw_math = true,
+                "--link-titles" => out.link_titles = true,
+                "--assets" => out.assets = Some(value()?),
+                "--assets-url" => out.assets_url = Some(value()?),
+                "--record" => out.record = Some(value()?),
+                "--replay" => out.replay = Some(value()?),
+                "--api-url" => out.api_url = Some(value()?),
+                "--notion-version" => out.notion_version = Some(value()?),
+                "--max-attempts" => {
+                    let attempts = value()?;
+                    out.max_attempts = Some(
+                        attempts
+                            .parse()
+                            .map_err(|_| format!("Invalid --max-attempts {:?}", attempts))?,
+                    )
+                }
+                "-" => out.file = Some(arg),
+                _ if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+                _ if out.page.is_none() => out.page = Some(arg),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        if out.page.is_some() && out.file.is_some() {
+            return Err("Give either a page id or --file, not both".to_string());
+        }
+        if out.assets_url.is_some() && out.assets.is_none() {
+            return Err("--assets-url needs --assets".to_string());
+        }
+        if out.record.is_some() && out.replay.is_some() {
+            return Err("Can't --record and --replay at the same time".to_string());
+        }
+        if out.file.is_some() && (out.record.is_some() || out.replay.is_some()) {
+            return Err("--record and --replay only work when fetching a page".to_string());
+        }
+
+        Ok(out)
+    }
+}
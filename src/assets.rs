@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+use reqwest::{header::CONTENT_TYPE, Url};
+
+use crate::error::{Error, Result};
+use crate::intermediary::{visit_mut, Block, Icon, Page};
+use crate::retry::RetryClient;
+
+/// Downloads the files a page links to and points the page at the local copies
+/// instead.
+///
+/// Files Notion hosts are served from pre-signed links that stop working after
+/// an hour, so HTML that links to them directly doesn't last. Each file is
+/// saved into a directory under a name made from a hash of its contents, so the
+/// same file is only ever stored once.
+pub struct Assets {
+    client: RetryClient,
+    dir: PathBuf,
+    url_prefix: String,
+    /// Original URL to the local one, so each file is only fetched once.
+    done: HashMap<String, String>,
+}
+
+impl Assets {
+    /// Saves files into `dir`, which is created if needed. Rewritten links
+    /// are relative, as if the HTML sits next to `dir` (so `/srv/site/assets`
+    /// gives `assets/<file>`), unless [`Assets::with_url_prefix`] says
+    /// otherwise.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        // the real name, so `.` and `..` work too
+        let url_prefix = fs::canonicalize(&dir)?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| ".".to_string());
+
+        let client = RetryClient::with_default_client()?;
+
+        Ok(Self {
+            client,
+            url_prefix,
+            dir,
+            done: HashMap::new(),
+        })
+    }
+
+    /// What to put in front of a file's name in rewritten links, for when the
+    /// HTML doesn't end up next to the assets directory.
+    pub fn with_url_prefix(mut self, prefix: &str) -> Self {
+        self.url_prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Downloads every file in the tree and rewrites the blocks to use them.
+    /// A file that can't be downloaded keeps its original URL.
+    pub fn localize_blocks(&mut self, blocks: &mut [Block]) {
+        visit_mut(blocks, &mut |block| match block {
+            Block::Image { url, .. } | Block::Media { url, .. } => self.localize(url),
+            Block::Callout {
+                icon: Some(Icon::Image(url)),
+                ..
+            } => self.localize(url),
+            _ => {}
+        });
+    }
+
+    /// Downloads the page's cover and icon.
+    pub fn localize_page(&mut self, page: &mut Page) {
+        if let Some(cover) = &mut page.cover {
+            self.localize(cover);
+        }
+        if let Some(Icon::Image(url)) = &mut page.icon {
+            self.localize(url);
+        }
+    }
+
+    fn localize(&mut self, url: &mut String) {
+        if let Some(local) = self.done.get(url.as_str()) {
+            *url = local.clone();
+            return;
+        }
+
+        match self.download(url) {
+            Ok(Some(name)) => {
+                let local = format!("{}/{}", self.url_prefix, name);
+                self.done.insert(url.clone(), local.clone());
+                *url = local;
+            }
+            Ok(None) => {}
+            Err(err) => warn!("Couldn't download {}, keeping the link: {}", url, err),
+        }
+    }
+
+    /// Saves the file at `url`, returning its name in the assets directory.
+    /// Links to web pages rather than files are left alone.
+    fn download(&self, url: &str) -> Result<Option<String>> {
+        let parsed = match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => parsed,
+            _ => return Ok(None),
+        };
+
+        debug!("Downloading {}", url);
+        let res = self.client.send(self.client.get(url))?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Error::Http {
+                status,
+                code: None,
+                message: None,
+            });
+        }
+
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(';').next().unwrap_or(value).trim().to_string())
+            .unwrap_or_default();
+        if content_type == "text/html" {
+            debug!("{} is a web page, not downloading it", url);
+            return Ok(None);
+        }
+
+        let bytes = res.bytes()?;
+        let name = format!(
+            "{:016x}.{}",
+            fnv1a(&bytes),
+            extension(&parsed, &content_type)
+        );
+
+        let path = self.dir.join(&name);
+        if !path.exists() {
+            fs::write(&path, &bytes)?;
+            info!("Saved {} as {}", parsed.path(), path.display());
+        }
+
+        Ok(Some(name))
+    }
+}
+
+/// A small, stable hash, so the same file always gets the same name.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// The extension from the URL if it has a sensible one, otherwise a guess
+/// from the content type.
+fn extension(url: &Url, content_type: &str) -> String {
+    let from_path = Path::new(url.path())
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|ext| ext.to_ascii_lowercase());
+    if let Some(ext) = from_path {
+        return ext;
+    }
+
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/avif" => "avif",
+        "application/pdf" => "pdf",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "video/quicktime" => "mov",
+        "audio/mpeg" => "mp3",
+        "audio/ogg" => "ogg",
+        "audio/wav" | "audio/x-wav" => "wav",
+        _ => "bin",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Assets;
+
+    #[test]
+    fn links_are_relative_to_the_directory() {
+        let dir = std::env::temp_dir()
+            .join(format!("notion2html-assets-{}", std::process::id()))
+            .join("assets");
+        let assets = Assets::new(&dir).unwrap();
+        let nested = Assets::new(dir.join("..").join("assets")).unwrap();
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+
+        assert_eq!(assets.url_prefix, "assets");
+        assert_eq!(nested.url_prefix, "assets");
+        assert_eq!(assets.with_url_prefix("/static/").url_prefix, "/static");
+    }
+}
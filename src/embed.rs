@@ -0,0 +1,354 @@
+use reqwest::Url;
+
+/// Hosts whose pages are put in an `<iframe>` unless told otherwise. Links to
+/// these get turned into each provider's own embed URL.
+pub const DEFAULT_EMBED_HOSTS: &[&str] = &[
+    "youtube.com",
+    "youtu.be",
+    "vimeo.com",
+    "figma.com",
+    "codepen.io",
+    "gist.github.com",
+    "google.com",
+];
+
+/// How to show an embedded page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Embed {
+    /// An `<iframe>` of `src`, with `style` sizing it.
+    Frame { src: String, style: &'static str },
+    /// An `<iframe>` with this HTML as its `srcdoc`, for providers that only
+    /// offer a `<script>` to embed.
+    Document(String),
+}
+
+const VIDEO: &str = "width:100%;aspect-ratio:16/9;border:0";
+const PAGE: &str = "width:100%;height:32rem;border:0";
+
+/// How to embed `url`, or `None` if it should just be linked to: either its
+/// host isn't in `hosts` (a host also allows its subdomains) or it's on a
+/// provider we know but isn't something that provider can embed.
+pub fn embed(url: &str, hosts: &[String]) -> Option<Embed> {
+    let url = Url::parse(url).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = url.host_str()?.to_ascii_lowercase();
+    let on = |domain: &str| host == domain || host.ends_with(&format!(".{}", domain));
+
+    if !hosts
+        .iter()
+        .any(|allowed| on(allowed.trim().trim_start_matches('.')))
+    {
+        return None;
+    }
+
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+
+    if on("youtube.com") || on("youtube-nocookie.com") || on("youtu.be") {
+        let id = match segments.as_slice() {
+            [id] if on("youtu.be") => id.to_string(),
+            ["watch"] => query("v")?,
+            ["embed" | "shorts" | "live" | "v", id, ..] => id.to_string(),
+            _ => return None,
+        };
+        if !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return None;
+        }
+        let mut src = format!("https://www.youtube-nocookie.com/embed/{}", id);
+        if let Some(start) = query("t")
+            .or_else(|| query("start"))
+            .and_then(|t| seconds(&t))
+        {
+            src += &format!("?start={}", start);
+        }
+        return Some(Embed::Frame { src, style: VIDEO });
+    }
+
+    if on("vimeo.com") {
+        let id = match segments.as_slice() {
+            ["video", id, ..] if host == "player.vimeo.com" => id,
+            // vimeo.com/<id>, or /channels/<name>/<id> and the like
+            [.., id] => id,
+            _ => return None,
+        };
+        if !id.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        return Some(Embed::Frame {
+            src: format!("https://player.vimeo.com/video/{}", id),
+            style: VIDEO,
+        });
+    }
+
+    if on("figma.com") {
+        if !matches!(
+            segments.first(),
+            Some(&("file" | "design" | "proto" | "board" | "slides"))
+        ) {
+            return None;
+        }
+        let src = Url::parse_with_params(
+            "https://www.figma.com/embed",
+            &[
+                ("embed_host", env!("CARGO_PKG_NAME")),
+                ("url", url.as_str()),
+            ],
+        )
+        .ok()?;
+        return Some(Embed::Frame {
+            src: src.to_string(),
+            style: PAGE,
+        });
+    }
+
+    if on("codepen.io") {
+        let [user, "pen" | "embed" | "full" | "details", id, ..] = segments.as_slice() else {
+            return None;
+        };
+        return Some(Embed::Frame {
+            src: format!(
+                "https://codepen.io/{}/embed/{}?default-tab=result",
+                user, id
+            ),
+            style: PAGE,
+        });
+    }
+
+    if on("gist.github.com") {
+        let [user, id] = segments.as_slice() else {
+            return None;
+        };
+        let id = id.trim_end_matches(".js");
+        if !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        // gists only come as a script that writes the gist into the page
+        return Some(Embed::Document(format!(
+            "<base target=\"_blank\" /><script src=\"https://gist.github.com/{}/{}.js\"></script>",
+            user, id
+        )));
+    }
+
+    if on("google.com") {
+        let is_maps = host.starts_with("maps.") || segments.first() == Some(&"maps");
+        if !is_maps {
+            return None;
+        }
+        if segments.get(1) == Some(&"embed") {
+            return Some(Embed::Frame {
+                src: url.to_string(),
+                style: PAGE,
+            });
+        }
+
+        // /maps/place/<name>/@<lat>,<lng>,<zoom>z/..., /maps/@..., /maps?q=...
+        // all kept percent-encoded, since they're going into a query anyway
+        let q = url
+            .query()
+            .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("q=")));
+        let place = segments
+            .iter()
+            .position(|s| *s == "place" || *s == "search")
+            .and_then(|i| segments.get(i + 1));
+        let at = segments
+            .iter()
+            .find_map(|s| s.strip_prefix('@'))
+            .map(|at| at.split(',').collect::<Vec<_>>());
+        let mut query = match (q.or(place.copied()), &at) {
+            (Some(q), _) => format!("q={}", q),
+            (None, Some(at)) if at.len() >= 2 => format!("q={},{}", at[0], at[1]),
+            _ => return None,
+        };
+        if let Some(zoom) = at
+            .as_ref()
+            .and_then(|at| at.get(2))
+            .and_then(|z| z.strip_suffix('z'))
+        {
+            query += &format!("&z={}", zoom.split('.').next().unwrap_or(zoom));
+        }
+        query += "&output=embed";
+        return Some(Embed::Frame {
+            src: format!("https://maps.google.com/maps?{}", query),
+            style: PAGE,
+        });
+    }
+
+    // an allowed host we don't know anything special about
+    Some(Embed::Frame {
+        src: url.to_string(),
+        style: PAGE,
+    })
+}
+
+/// How to embed a video block's `url` if it's a page on a video site (like
+/// YouTube) rather than a video file, which a `<video>` can't play.
+pub fn video(url: &str, hosts: &[String]) -> Option<Embed> {
+    match embed(url, hosts)? {
+        // only providers we know are video sites get sized like one
+        frame @ Embed::Frame { style: VIDEO, .. } => Some(frame),
+        _ => None,
+    }
+}
+
+/// YouTube's `t=` as seconds: `90`, `90s` or `1h2m3s`.
+fn seconds(t: &str) -> Option<u64> {
+    if let Ok(seconds) = t.parse() {
+        return Some(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in t.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' => {
+                let n: u64 = number.parse().ok()?;
+                number.clear();
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                // a silly `t=` is just ignored, rather than overflowing
+                total = n.checked_mul(unit)?.checked_add(total)?;
+            }
+            _ => return None,
+        }
+    }
+    number.is_empty().then_some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{embed, Embed, DEFAULT_EMBED_HOSTS, PAGE, VIDEO};
+
+    fn src(url: &str) -> Option<String> {
+        let hosts: Vec<String> = DEFAULT_EMBED_HOSTS.iter().map(|h| h.to_string()).collect();
+        match embed(url, &hosts)? {
+            Embed::Frame { src, .. } => Some(src),
+            Embed::Document(html) => Some(html),
+        }
+    }
+
+    #[test]
+    fn youtube() {
+        let embed = "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ";
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/shorts/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(src(url).as_deref(), Some(embed), "{}", url);
+        }
+        assert_eq!(src("https://www.youtube.com/@channel"), None);
+    }
+
+    #[test]
+    fn youtube_start_time() {
+        assert_eq!(
+            src("https://youtu.be/abc?t=90").unwrap(),
+            "https://www.youtube-nocookie.com/embed/abc?start=90"
+        );
+        assert_eq!(
+            src("https://www.youtube.com/watch?v=abc&t=1h2m3s").unwrap(),
+            "https://www.youtube-nocookie.com/embed/abc?start=3723"
+        );
+        assert_eq!(
+            src("https://youtu.be/abc?t=10000000000000000h").unwrap(),
+            "https://www.youtube-nocookie.com/embed/abc"
+        );
+    }
+
+    #[test]
+    fn vimeo() {
+        for url in [
+            "https://vimeo.com/76979871",
+            "https://vimeo.com/channels/staffpicks/76979871",
+            "https://player.vimeo.com/video/76979871",
+        ] {
+            assert_eq!(
+                src(url).as_deref(),
+                Some("https://player.vimeo.com/video/76979871"),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(src("https://vimeo.com/channels/staffpicks"), None);
+    }
+
+    #[test]
+    fn figma() {
+        assert_eq!(
+            src("https://www.figma.com/file/abc/Title").unwrap(),
+            format!(
+                "https://www.figma.com/embed?embed_host={}&url=https%3A%2F%2Fwww.figma.com%2Ffile%2Fabc%2FTitle",
+                env!("CARGO_PKG_NAME")
+            )
+        );
+        assert_eq!(src("https://www.figma.com/community"), None);
+    }
+
+    #[test]
+    fn codepen() {
+        assert_eq!(
+            src("https://codepen.io/someone/pen/abcdef").unwrap(),
+            "https://codepen.io/someone/embed/abcdef?default-tab=result"
+        );
+    }
+
+    #[test]
+    fn gist() {
+        assert_eq!(
+            src("https://gist.github.com/someone/0123abcd").unwrap(),
+            "<base target=\"_blank\" /><script src=\"https://gist.github.com/someone/0123abcd.js\"></script>"
+        );
+    }
+
+    #[test]
+    fn google_maps() {
+        assert_eq!(
+            src("https://www.google.com/maps/place/Eiffel+Tower/@48.8583701,2.2944813,17z/data=x")
+                .unwrap(),
+            "https://maps.google.com/maps?q=Eiffel+Tower&z=17&output=embed"
+        );
+        assert_eq!(
+            src("https://www.google.com/maps/@48.8583701,2.2944813,15.5z").unwrap(),
+            "https://maps.google.com/maps?q=48.8583701,2.2944813&z=15&output=embed"
+        );
+        assert_eq!(src("https://www.google.com/search?q=maps"), None);
+    }
+
+    #[test]
+    fn allowlist() {
+        assert_eq!(src("https://example.com/page"), None);
+        // a host that only ends with an allowed one isn't on it
+        assert_eq!(src("https://notyoutube.com/watch?v=abc"), None);
+
+        let hosts = vec!["example.com".to_string()];
+        assert_eq!(
+            embed("https://docs.example.com/page", &hosts),
+            Some(Embed::Frame {
+                src: "https://docs.example.com/page".to_string(),
+                style: PAGE,
+            })
+        );
+        assert_eq!(embed("https://youtu.be/abc", &hosts), None);
+        assert!(matches!(
+            embed("https://youtu.be/abc", &["youtu.be".to_string()]),
+            Some(Embed::Frame { style: VIDEO, .. })
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::error::Category;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request never got a response (DNS, TLS, connection reset, ...).
+    Transport(reqwest::Error),
+    /// Notion (or wherever a file was downloaded from) answered with a
+    /// non-success status. `code` and `message` come from Notion's error body
+    /// when it sent one.
+    Http {
+        status: StatusCode,
+        code: Option<String>,
+        message: Option<String>,
+    },
+    /// The response wasn't the JSON we expected.
+    Json(serde_json::Error),
+    /// The response was valid, but contains something we can't convert.
+    Unsupported(String),
+    /// Reading a saved response failed.
+    Io(std::io::Error),
+    /// The page id or URL we were given doesn't point at a page.
+    InvalidId(String),
+}
+
+impl Error {
+    /// Builds an [`Error::Http`] from a failed response, reading Notion's
+    /// `{"code": ..., "message": ...}` body if there is one.
+    pub(crate) fn from_response(status: StatusCode, body: &str) -> Self {
+        #[derive(Deserialize)]
+        struct NotionError {
+            code: Option<String>,
+            message: Option<String>,
+        }
+
+        let (code, message) = match serde_json::from_str::<NotionError>(body) {
+            Ok(err) => (err.code, err.message),
+            Err(_) => (None, None),
+        };
+
+        Error::Http {
+            status,
+            code,
+            message,
+        }
+    }
+
+    /// Sorts a deserialization error into [`Error::Io`] when reading the
+    /// JSON failed, and [`Error::Json`] when it was read but isn't a response
+    /// we understand. Blocks we don't know aren't an error at all, they're
+    /// kept as [`crate::notion::BlockData::Unknown`].
+    pub(crate) fn from_json(err: serde_json::Error) -> Self {
+        match err.classify() {
+            Category::Io => Error::Io(err.into()),
+            Category::Syntax | Category::Data | Category::Eof => Error::Json(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Transport(err) => write!(f, "request failed: {}", err),
+            Error::Http {
+                status,
+                code,
+                message,
+            } => {
+                write!(f, "got {}", status)?;
+                if let Some(code) = code {
+                    write!(f, " ({})", code)?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            Error::Json(err) => write!(f, "failed to parse JSON: {}", err),
+            Error::Unsupported(what) => write!(f, "unsupported content: {}", what),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::InvalidId(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Transport(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+
+use crate::error::{Error, Result};
+use crate::notion::{Block, BlockData, Page, PageResponse};
+use crate::retry::RetryClient;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.notion.com/v1";
+pub const DEFAULT_NOTION_VERSION: &str = "2022-06-28";
+
+pub struct Fetcher {
+    client: RetryClient,
+    token: String,
+    base_url: String,
+    notion_version: String,
+    fixtures: Option<Fixtures>,
+}
+
+/// Where API responses are saved to or served from, see
+/// [`Fetcher::with_fixtures`].
+#[derive(Debug, Clone)]
+pub enum Fixtures {
+    /// Fetch from Notion as usual, writing every response into the directory,
+    /// failed ones included.
+    Record(PathBuf),
+    /// Never touch the network, read every response from the directory. A
+    /// recorded failure fails the same way it did when it was recorded.
+    Replay(PathBuf),
+}
+
+impl Fetcher {
+    pub fn new(token: String) -> Result<Self> {
+        let client = RetryClient::with_default_client()?;
+
+        debug!("Created reqwest client");
+
+        Ok(Self {
+            client,
+            token,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            notion_version: DEFAULT_NOTION_VERSION.to_string(),
+            fixtures: None,
+        })
+    }
+
+    /// Sends requests somewhere other than `https://api.notion.com/v1`, like a
+    /// mock server. Endpoint paths are appended to this as-is.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the `Notion-Version` header, defaulting to
+    /// [`DEFAULT_NOTION_VERSION`]. Newer versions may return blocks we can't
+    /// read yet, which end up as [`BlockData::Unknown`].
+    pub fn with_notion_version(mut self, notion_version: &str) -> Self {
+        self.notion_version = notion_version.to_string();
+        self
+    }
+
+    /// Records responses into, or replays them from, a fixture directory.
+    /// Each response is stored as its own file, named after the endpoint and
+    /// the pagination cursor it was fetched with.
+    pub fn with_fixtures(mut self, fixtures: Fixtures) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// See [`RetryClient::with_max_attempts`].
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.client = self.client.with_max_attempts(max_attempts);
+        self
+    }
+
+    /// Fetches a page's title, icon, cover and properties. The content of the
+    /// page comes from [`Fetcher::block_tree`].
+    pub fn page(&self, id: &str) -> Result<Page> {
+        let body = self.get(&format!("pages/{}", id), &[], None)?;
+        let page = serde_json::from_str(&body).map_err(Error::from_json)?;
+        info!("Fetched page {}", id);
+        Ok(page)
+    }
+
+    /// Fetches every child of a block, and every child of those children, so
+    /// the returned blocks are the whole tree under `id`.
+    pub fn block_tree(&self, id: &str) -> Result<Vec<Block>> {
+        let mut blocks = self.block_children(id)?;
+        self.fill_children(&mut blocks)?;
+        Ok(blocks)
+    }
+
+    fn fill_children(&self, blocks: &mut [Block]) -> Result<()> {
+        for block in blocks {
+            if !block.has_children {
+                continue;
+            }
+            // child pages and databases are their own documents
+            if let BlockData::ChildPage { .. } | BlockData::ChildDatabase { .. } = block.block {
+                continue;
+            }
+
+            match block.block.children_mut() {
+                Some(slot) => {
+                    debug!("Fetching children of {} block {}", block.ty, block.id);
+                    *slot = Some(self.block_tree(&block.id)?);
+                }
+                None => warn!(
+                    "Ignoring children of {} block {}, nowhere to put them",
+                    block.ty, block.id
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches every child of a block, following `next_cursor` until Notion
+    /// says there's nothing left.
+    pub fn block_children(&self, id: &str) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut page = 1;
+
+        loop {
+            let body = self.get(
+                &format!("blocks/{}/children", id),
+                &[("page_size", "100")],
+                cursor.as_deref(),
+            )?;
+
+            let data: PageResponse = serde_json::from_str(&body).map_err(Error::from_json)?;
+
+            info!(
+                "Fetched {} blocks from page {} of {}",
+                data.results.len(),
+                page,
+                id
+            );
+
+            blocks.extend(data.results);
+
+            match data.next_cursor {
+                Some(next) if data.has_more => cursor = Some(next),
+                _ => break,
+            }
+            page += 1;
+        }
+
+        Ok(blocks)
+    }
+
+    /// GETs an API endpoint and returns the body, going through the fixture
+    /// directory if there is one.
+    fn get(&self, path: &str, query: &[(&str, &str)], cursor: Option<&str>) -> Result<String> {
+        let fixture = self.fixtures.as_ref().map(|fixtures| match fixtures {
+            Fixtures::Record(dir) | Fixtures::Replay(dir) => dir.join(fixture_name(path, cursor)),
+        });
+
+        if let (Some(Fixtures::Replay(_)), Some(fixture)) = (&self.fixtures, &fixture) {
+            debug!("Replaying {} from {}", path, fixture.display());
+            return replay(path, fixture);
+        }
+
+        let mut url = format!("{}/{}", self.base_url, path);
+        let mut separator = '?';
+        for (key, value) in query
+            .iter()
+            .copied()
+            .chain(cursor.map(|cursor| ("start_cursor", cursor)))
+        {
+            url += &format!("{}{}={}", separator, key, value);
+            separator = '&';
+        }
+
+        let req = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.token)
+            .header("Notion-Version", &self.notion_version);
+        let res = self.client.send(req)?;
+        let status = res.status();
+
+        debug!("Fetched {} with status {}", path, status);
+
+        let body = res.text()?;
+
+        if let Some(fixture) = fixture {
+            debug!("Recording {} to {}", path, fixture.display());
+            record(&fixture, status, &body)?;
+        }
+
+        if !status.is_success() {
+            return Err(Error::from_response(status, &body));
+        }
+
+        Ok(body)
+    }
+}
+
+/// Where the status of a failed response is kept, next to its body. Only
+/// failures get one, so a successful response's fixture is just what Notion
+/// sent.
+fn status_file(fixture: &Path) -> PathBuf {
+    fixture.with_extension("status")
+}
+
+fn record(fixture: &Path, status: StatusCode, body: &str) -> Result<()> {
+    if let Some(dir) = fixture.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(fixture, body)?;
+
+    let status_file = status_file(fixture);
+    if !status.is_success() {
+        fs::write(&status_file, status.as_str())?;
+    } else if status_file.exists() {
+        // the request failed the last time it was recorded
+        fs::remove_file(&status_file)?;
+    }
+    Ok(())
+}
+
+fn replay(path: &str, fixture: &Path) -> Result<String> {
+    let body = fs::read_to_string(fixture).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => Error::Io(io::Error::new(
+            ErrorKind::NotFound,
+            format!("no fixture for {} at {}", path, fixture.display()),
+        )),
+        _ => Error::Io(err),
+    })?;
+
+    let status_file = status_file(fixture);
+    let status = match fs::read_to_string(&status_file) {
+        Ok(status) => StatusCode::from_bytes(status.trim().as_bytes()).map_err(|_| {
+            Error::Io(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?} in {} isn't a status code",
+                    status,
+                    status_file.display()
+                ),
+            ))
+        })?,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(body),
+        Err(err) => return Err(Error::Io(err)),
+    };
+
+    if !status.is_success() {
+        return Err(Error::from_response(status, &body));
+    }
+    Ok(body)
+}
+
+/// `blocks/abc/children` with cursor `xyz` becomes `blocks_abc_children@xyz.json`.
+fn fixture_name(path: &str, cursor: Option<&str>) -> String {
+    let clean = |s: &str| {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+    };
+
+    match cursor {
+        Some(cursor) => format!("{}@{}.json", clean(path), clean(cursor)),
+        None => format!("{}.json", clean(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use reqwest::StatusCode;
+
+    use super::{fixture_name, Fetcher, Fixtures};
+    use crate::error::Error;
+
+    #[test]
+    fn fixture_names() {
+        assert_eq!(fixture_name("pages/abc-123", None), "pages_abc-123.json");
+        assert_eq!(
+            fixture_name("blocks/abc/children", Some("d3f/4.5=")),
+            "blocks_abc_children@d3f_4_5_.json"
+        );
+    }
+
+    #[test]
+    fn replays_failures() {
+        let dir = std::env::temp_dir().join(format!("notion2html-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pages_ok.json"), "{}").unwrap();
+        fs::write(
+            dir.join("pages_gone.json"),
+            r#"{"object":"error","code":"object_not_found","message":"Could not find page"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("pages_gone.status"), "404").unwrap();
+
+        let fetcher = Fetcher::new(String::new())
+            .unwrap()
+            .with_fixtures(Fixtures::Replay(dir.clone()));
+        let ok = fetcher.get("pages/ok", &[], None);
+        let gone = fetcher.get("pages/gone", &[], None);
+        let missing = fetcher.get("pages/missing", &[], None);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ok.unwrap(), "{}");
+        match gone {
+            Err(Error::Http {
+                status: StatusCode::NOT_FOUND,
+                code: Some(code),
+                ..
+            }) => assert_eq!(code, "object_not_found"),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(missing, Err(Error::Io(_))));
+    }
+}
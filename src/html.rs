@@ -1,26 +1,172 @@
 use log::trace;
 use log::warn;
+use reqwest::Url;
 
+use crate::embed::{embed, video, Embed, DEFAULT_EMBED_HOSTS};
 use crate::intermediary::Block;
+use crate::intermediary::Icon;
+use crate::intermediary::IntermediaryColor;
+use crate::intermediary::ListItem;
+use crate::intermediary::MediaKind;
+use crate::intermediary::Page;
+use crate::intermediary::PropertyValue;
 use crate::intermediary::RichText;
+use crate::math::to_mathml;
 
-pub fn from_blocks(blocks: Vec<Block>, extra: bool) -> String {
+/// Renders a whole HTML document: a header with the page's cover, icon, title
+/// and properties, followed by its content.
+pub fn from_page(page: Page, blocks: Vec<Block>, options: &RenderOptions) -> String {
+    let title: String = page.title.iter().map(|t| t.plain_text.as_str()).collect();
+
+    let mut out = String::from("<!DOCTYPE html><html><head><meta charset=\"utf-8\" />");
+    out += "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\" />";
+    out += &format!("<title>{}</title></head><body><article>", escape(&title));
+
+    if let Some(cover) = page.cover {
+        out += &format!(
+            "<img class=\"cover\" src=\"{}\" alt=\"\" />",
+            escape(&cover)
+        );
+    }
+
+    out += "<header>";
+    match page.icon {
+        Some(Icon::Emoji(emoji)) => {
+            out += &format!("<span class=\"icon\">{}</span>", escape(&emoji))
+        }
+        Some(Icon::Image(url)) => {
+            out += &format!("<img class=\"icon\" src=\"{}\" alt=\"\" />", escape(&url))
+        }
+        None => {}
+    }
+    out += &format!("<h1>{}</h1>", rich_text_to_html(page.title));
+
+    out += "<dl class=\"properties\">";
+    out += &format!("<dt>Created</dt><dd>{}</dd>", time(&page.created));
+    out += &format!("<dt>Last edited</dt><dd>{}</dd>", time(&page.last_edited));
+    for property in page.properties {
+        let value = match property.value {
+            PropertyValue::Text(text) => rich_text_to_html(text),
+            PropertyValue::Tags(tags) => tags
+                .iter()
+                .map(|tag| format!("<span class=\"tag\">{}</span>", escape(tag)))
+                .collect(),
+            PropertyValue::Date { start, end: None } => time(&start),
+            PropertyValue::Date {
+                start,
+                end: Some(end),
+            } => format!("{} → {}", time(&start), time(&end)),
+            PropertyValue::Checkbox(checked) => format!(
+                "<input type=\"checkbox\" disabled {}/>",
+                if checked { "checked " } else { "" }
+            ),
+            PropertyValue::Link { href, text } => {
+                format!("<a href=\"{}\">{}</a>", escape(&href), escape(&text))
+            }
+        };
+        out += &format!("<dt>{}</dt><dd>{}</dd>", escape(&property.name), value);
+    }
+    out += "</dl></header>";
+
+    out += &from_blocks(blocks, options);
+    out += "</article></body></html>";
+
+    out
+}
+
+/// Knobs for how blocks are turned into HTML.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Render toggles and toggleable headings open instead of collapsed.
+    pub expand_toggles: bool,
+    /// Hosts that embeds are shown inline from, in a sandboxed `<iframe>`.
+    /// Embeds from anywhere else become links. Defaults to
+    /// [`DEFAULT_EMBED_HOSTS`].
+    pub embed_hosts: Vec<String>,
+    /// Leave equations as LaTeX in `\[...\]` for something like KaTeX or
+    /// MathJax to render in the browser, instead of converting them to
+    /// MathML.
+    pub raw_math: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            expand_toggles: false,
+            embed_hosts: DEFAULT_EMBED_HOSTS.iter().map(|h| h.to_string()).collect(),
+            raw_math: false,
+        }
+    }
+}
+
+/// Where we are while rendering.
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    options: &'a RenderOptions,
+    /// How many lists deep we are, which picks the numbering style of
+    /// numbered lists.
+    depth: usize,
+}
+
+impl Context<'_> {
+    fn nested(self) -> Self {
+        Context {
+            depth: self.depth + 1,
+            ..self
+        }
+    }
+}
+
+pub fn from_blocks(blocks: Vec<Block>, options: &RenderOptions) -> String {
+    render(blocks, false, Context { options, depth: 0 })
+}
+
+fn render(blocks: Vec<Block>, extra: bool, ctx: Context) -> String {
     let mut out = String::new();
 
     for block in preprocess(blocks) {
         match block {
-            Block::Header { rich_text, size } => {
-                out += &format!("<h{}>{}</h{}>", size, rich_text_to_html(rich_text), size);
+            Block::Header {
+                rich_text,
+                size,
+                anchor,
+                children,
+            } => {
+                let heading = format!(
+                    "<h{}{}>{}</h{}>",
+                    size,
+                    anchor
+                        .map(|anchor| format!(" id=\"{}\"", escape(&anchor)))
+                        .unwrap_or_default(),
+                    rich_text_to_html(rich_text),
+                    size
+                );
+                match children {
+                    Some(children) => {
+                        out += &format!(
+                            "<details{}><summary>{}</summary>{}</details>",
+                            if ctx.options.expand_toggles {
+                                " open"
+                            } else {
+                                ""
+                            },
+                            heading,
+                            render(children, false, ctx)
+                        );
+                    }
+                    None => out += &heading,
+                }
             }
             Block::Divider => out += "<hr />",
             Block::Quote {
                 rich_text,
                 children,
             } => {
-                out += &format!("<blockquote>{}</blockquote>", rich_text_to_html(rich_text));
-                if let Some(children) = children {
-                    out += &from_blocks(children, false);
-                }
+                out += &format!(
+                    "<blockquote>{}{}</blockquote>",
+                    rich_text_to_html(rich_text),
+                    render(children.unwrap_or_default(), false, ctx)
+                );
             }
             Block::CodeBlock { text, lang } => {
                 out += &format!(
@@ -31,31 +177,51 @@ pub fn from_blocks(blocks: Vec<Block>, extra: bool) -> String {
             Block::List { items } => {
                 out += "<ul>";
                 for item in items {
-                    let h = from_blocks(vec![item], true);
-                    out += &format!("<li>{}</li>", h);
+                    out += &list_item(item, ctx);
                 }
                 out += "</ul>";
             }
-            Block::NumberedList { items } => {
-                out += "<ol>";
+            Block::NumberedList { start, items } => {
+                out += "<ol";
+                // like notion, go 1. -> a. -> i. -> 1. as lists nest
+                match ctx.depth % 3 {
+                    1 => out += " type=\"a\"",
+                    2 => out += " type=\"i\"",
+                    _ => {}
+                }
+                if let Some(start) = start.filter(|&start| start != 1) {
+                    out += &format!(" start=\"{}\"", start);
+                }
+                out += ">";
                 for item in items {
-                    let h = from_blocks(vec![item], true);
-                    out += &format!("<li>{}</li>", h);
+                    out += &list_item(item, ctx);
                 }
                 out += "</ol>";
             }
             Block::TodoList { items: list } => {
                 out += "<ul>";
-                for (checked, item) in list {
+                for item in list {
+                    let text = render(
+                        vec![Block::Line {
+                            rich_text: item.rich_text,
+                            children: vec![],
+                        }],
+                        false,
+                        ctx,
+                    );
                     out += &format!(
-                        "<li><input type=\"checkbox\" {}>{}</li>",
-                        if checked { "checked" } else { "" },
-                        from_blocks(vec![item], false)
+                        "<li><input type=\"checkbox\" {}>{}{}</li>",
+                        if item.checked { "checked" } else { "" },
+                        text,
+                        render(item.children, false, ctx.nested())
                     );
                 }
                 out += "</ul>";
             }
-            Block::Line { rich_text } => {
+            Block::Line {
+                rich_text,
+                children,
+            } => {
                 if rich_text.is_empty() {
                     out += "<br />";
                 } else {
@@ -64,6 +230,222 @@ pub fn from_blocks(blocks: Vec<Block>, extra: bool) -> String {
                     }
                     out += &format!("<p>{}</p>", rich_text_to_html(rich_text));
                 }
+                if !children.is_empty() {
+                    out += &format!(
+                        "<div class=\"indent\">{}</div>",
+                        render(children, false, ctx)
+                    );
+                }
+            }
+            Block::Toggle {
+                rich_text,
+                children,
+            } => {
+                out += &format!(
+                    "<details{}><summary>{}</summary>{}</details>",
+                    if ctx.options.expand_toggles {
+                        " open"
+                    } else {
+                        ""
+                    },
+                    rich_text_to_html(rich_text),
+                    render(children, false, ctx)
+                );
+            }
+            Block::Callout {
+                rich_text,
+                icon,
+                color,
+                background,
+                children,
+            } => {
+                out += &format!(
+                    "<aside class=\"callout callout-{}{}\">",
+                    color_name(&color),
+                    if background { "-background" } else { "" }
+                );
+                match icon {
+                    Some(Icon::Emoji(emoji)) => {
+                        out += &format!("<span class=\"callout-icon\">{}</span>", escape(&emoji))
+                    }
+                    Some(Icon::Image(url)) => {
+                        out += &format!(
+                            "<img class=\"callout-icon\" src=\"{}\" alt=\"\" />",
+                            escape(&url)
+                        )
+                    }
+                    None => {}
+                }
+                out += &format!(
+                    "<div class=\"callout-content\">{}{}</div></aside>",
+                    rich_text_to_html(rich_text),
+                    render(children, false, ctx)
+                );
+            }
+            Block::Table {
+                has_column_header,
+                has_row_header,
+                rows,
+            } => {
+                out += "<table>";
+                let mut rows = rows.into_iter();
+                if has_column_header {
+                    if let Some(header) = rows.next() {
+                        out += "<thead><tr>";
+                        for cell in header {
+                            out += &format!("<th scope=\"col\">{}</th>", rich_text_to_html(cell));
+                        }
+                        out += "</tr></thead>";
+                    }
+                }
+                out += "<tbody>";
+                for row in rows {
+                    out += "<tr>";
+                    for (i, cell) in row.into_iter().enumerate() {
+                        if i == 0 && has_row_header {
+                            out += &format!("<th scope=\"row\">{}</th>", rich_text_to_html(cell));
+                        } else {
+                            out += &format!("<td>{}</td>", rich_text_to_html(cell));
+                        }
+                    }
+                    out += "</tr>";
+                }
+                out += "</tbody></table>";
+            }
+            Block::Columns { columns } => {
+                // columns share the width by ratio, but wrap onto their own
+                // lines once they'd get narrower than 16rem
+                out += "<div class=\"columns\" style=\"display:flex;flex-wrap:wrap;gap:1rem\">";
+                let even = 1.0 / columns.len() as f64;
+                for column in columns {
+                    out += &format!(
+                        "<div class=\"column\" style=\"flex:{} 1 0;min-width:min(100%,16rem)\">{}</div>",
+                        // as a percentage, since grow factors that add up to
+                        // less than 1 leave part of the row empty
+                        (column
+                            .width_ratio
+                            .filter(|r| r.is_finite() && *r > 0.0)
+                            .unwrap_or(even)
+                            * 10000.0)
+                            .round()
+                            / 100.0,
+                        render(column.children, false, ctx)
+                    );
+                }
+                out += "</div>";
+            }
+            Block::Image { url, caption } => {
+                let alt: String = caption.iter().map(|t| t.plain_text.as_str()).collect();
+                out += &format!(
+                    "<figure><img src=\"{}\" alt=\"{}\" />",
+                    escape(&url),
+                    escape(&alt)
+                );
+                if !caption.is_empty() {
+                    out += &format!("<figcaption>{}</figcaption>", rich_text_to_html(caption));
+                }
+                out += "</figure>";
+            }
+            Block::Media {
+                kind,
+                url,
+                name,
+                caption,
+            } => {
+                let embed = match kind {
+                    MediaKind::Video => video(&url, &ctx.options.embed_hosts),
+                    _ => None,
+                };
+                let url = escape(&url);
+                // what browsers that can't play or show the file get instead
+                let link = format!("<a href=\"{}\">{}</a>", url, escape(&name));
+                out += &match (kind, embed) {
+                    (MediaKind::Video, Some(embed)) => {
+                        format!("<figure class=\"video\">{}", iframe(embed))
+                    }
+                    (MediaKind::Video, _) => format!(
+                        "<figure class=\"video\"><video src=\"{}\" controls preload=\"metadata\">{}</video>",
+                        url, link
+                    ),
+                    (MediaKind::Audio, _) => format!(
+                        "<figure class=\"audio\"><audio src=\"{}\" controls preload=\"metadata\">{}</audio>",
+                        url, link
+                    ),
+                    (MediaKind::Pdf, _) => format!(
+                        "<figure class=\"pdf\"><object data=\"{}\" type=\"application/pdf\" style=\"width:100%;aspect-ratio:1/1.414\">{}</object>",
+                        url, link
+                    ),
+                    (MediaKind::File, _) => format!(
+                        "<figure class=\"file\"><a class=\"file-card\" href=\"{}\" download>📎 {}</a>",
+                        url,
+                        escape(&name)
+                    ),
+                };
+                if !caption.is_empty() {
+                    out += &format!("<figcaption>{}</figcaption>", rich_text_to_html(caption));
+                }
+                out += "</figure>";
+            }
+            Block::Bookmark {
+                url,
+                title,
+                description,
+                caption,
+            } => {
+                let host = Url::parse(&url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(|host| host.to_string()))
+                    .unwrap_or_default();
+                out += &format!(
+                    "<figure class=\"bookmark\"><a class=\"link-card\" href=\"{}\">",
+                    escape(&url)
+                );
+                if let Some(title) = title {
+                    out += &format!("<span class=\"title\">{}</span>", escape(&title));
+                }
+                if let Some(description) = description {
+                    out += &format!(
+                        "<span class=\"description\">{}</span>",
+                        escape(&description)
+                    );
+                }
+                out += &format!("<span class=\"url\">{}</span>", escape(&url));
+                if !host.is_empty() {
+                    out += &format!("<span class=\"host\">{}</span>", escape(&host));
+                }
+                out += "</a>";
+                if !caption.is_empty() {
+                    out += &format!("<figcaption>{}</figcaption>", rich_text_to_html(caption));
+                }
+                out += "</figure>";
+            }
+            Block::Embed { url, caption } => {
+                out += "<figure class=\"embed\">";
+                out += &match embed(&url, &ctx.options.embed_hosts) {
+                    Some(embed) => iframe(embed),
+                    None => format!("<a href=\"{}\">{}</a>", escape(&url), escape(&url)),
+                };
+                if !caption.is_empty() {
+                    out += &format!("<figcaption>{}</figcaption>", rich_text_to_html(caption));
+                }
+                out += "</figure>";
+            }
+            Block::Equation { expression } => {
+                out += &if ctx.options.raw_math {
+                    format!(
+                        "<div class=\"equation\">\\[{}\\]</div>",
+                        escape(&expression)
+                    )
+                } else {
+                    format!("<div class=\"equation\">{}</div>", to_mathml(&expression))
+                };
+            }
+            Block::Unsupported { ty } => {
+                out += &format!(
+                    "<div class=\"unsupported\" data-type=\"{}\">Unsupported block: {}</div>",
+                    escape(&ty),
+                    escape(&ty)
+                );
             }
             _ => warn!("Can't find html block type for {:?}", block.to_string()),
         }
@@ -72,6 +454,38 @@ pub fn from_blocks(blocks: Vec<Block>, extra: bool) -> String {
     out
 }
 
+/// A sandboxed `<iframe>` showing an embedded page.
+fn iframe(embed: Embed) -> String {
+    match embed {
+        Embed::Frame { src, style } => format!(
+            "<iframe src=\"{}\" style=\"{}\" sandbox=\"allow-scripts allow-same-origin allow-popups allow-presentation\" allowfullscreen loading=\"lazy\" referrerpolicy=\"strict-origin-when-cross-origin\"></iframe>",
+            escape(&src),
+            style
+        ),
+        // no allow-same-origin, since a srcdoc frame would share ours and
+        // could lift its own sandbox
+        Embed::Document(doc) => format!(
+            "<iframe srcdoc=\"{}\" style=\"width:100%;height:32rem;border:0\" sandbox=\"allow-scripts allow-popups\" loading=\"lazy\"></iframe>",
+            escape(&doc)
+        ),
+    }
+}
+
+/// An `<li>` with the item's text, followed by any lists (or anything else)
+/// nested under it.
+fn list_item(item: ListItem, ctx: Context) -> String {
+    let text = render(
+        vec![Block::Line {
+            rich_text: item.rich_text,
+            children: vec![],
+        }],
+        true,
+        ctx,
+    );
+    let children = render(item.children, false, ctx.nested());
+    format!("<li>{}{}</li>", text, children)
+}
+
 fn preprocess(blocks: Vec<Block>) -> Vec<Block> {
     let mut out = vec![];
     // temporary placeholder
@@ -79,9 +493,19 @@ fn preprocess(blocks: Vec<Block>) -> Vec<Block> {
 
     for block in blocks {
         match block {
-            Block::Header { rich_text, size } => {
+            Block::Header {
+                rich_text,
+                size,
+                anchor,
+                children,
+            } => {
                 out.push(last_block);
-                last_block = Block::Header { rich_text, size };
+                last_block = Block::Header {
+                    rich_text,
+                    size,
+                    anchor,
+                    children,
+                };
             }
             Block::Divider => {
                 out.push(last_block);
@@ -89,12 +513,12 @@ fn preprocess(blocks: Vec<Block>) -> Vec<Block> {
             }
             Block::Quote {
                 rich_text,
-                children: _,
+                children,
             } => {
                 out.push(last_block);
                 last_block = Block::Quote {
                     rich_text,
-                    children: None,
+                    children,
                 };
             }
             Block::CodeBlock { text, lang } => {
@@ -112,44 +536,89 @@ fn preprocess(blocks: Vec<Block>) -> Vec<Block> {
                     last_block = Block::List { items };
                 }
             },
-            Block::NumberedList { items } => match last_block {
-                Block::NumberedList { items: last_items } => {
+            Block::NumberedList { start, items } => match last_block {
+                // keep going unless numbering was restarted at this item
+                Block::NumberedList {
+                    start: last_start,
+                    items: last_items,
+                } if start.is_none_or(|s| s == last_start.unwrap_or(1) + last_items.len()) => {
                     let mut new_items = last_items;
                     new_items.extend(items);
-                    last_block = Block::NumberedList { items: new_items };
+                    last_block = Block::NumberedList {
+                        start: last_start,
+                        items: new_items,
+                    };
                 }
                 _ => {
                     out.push(last_block);
-                    last_block = Block::NumberedList { items };
+                    last_block = Block::NumberedList { start, items };
                 }
             },
             Block::TodoList { items } => {
                 out.push(last_block);
                 last_block = Block::TodoList { items };
             }
-            Block::Line { rich_text } => {
+            Block::Line {
+                rich_text,
+                children,
+            } => {
                 if rich_text.is_empty() {
                     out.push(last_block);
-                    last_block = Block::Line { rich_text };
+                    last_block = Block::Line {
+                        rich_text,
+                        children,
+                    };
                 } else {
                     match last_block {
+                        // anything indented under the last line has to come
+                        // right after it, so only merge into lines without
                         Block::Line {
                             rich_text: last_rich_text,
-                        } => {
+                            children: last_children,
+                        } if last_children.is_empty() => {
                             let mut new_rich_text = last_rich_text;
                             new_rich_text.push(RichText::default());
                             new_rich_text.extend(rich_text);
                             last_block = Block::Line {
                                 rich_text: new_rich_text,
+                                children,
                             };
                         }
                         _ => {
                             out.push(last_block);
-                            last_block = Block::Line { rich_text };
+                            last_block = Block::Line {
+                                rich_text,
+                                children,
+                            };
                         }
                     }
                 }
             }
+            Block::Toggle {
+                rich_text,
+                children,
+            } => {
+                out.push(last_block);
+                last_block = Block::Toggle {
+                    rich_text,
+                    children,
+                };
+            }
+            Block::Callout { .. }
+            | Block::Table { .. }
+            | Block::Columns { .. }
+            | Block::Image { .. }
+            | Block::Media { .. }
+            | Block::Bookmark { .. }
+            | Block::Embed { .. }
+            | Block::Equation { .. } => {
+                out.push(last_block);
+                last_block = block;
+            }
+            Block::Unsupported { ty } => {
+                out.push(last_block);
+                last_block = Block::Unsupported { ty };
+            }
             _ => warn!(
                 "Can't find intermediary block type for {:?}",
                 block.to_string()
@@ -200,3 +669,59 @@ fn rich_text_to_html(rich_text: Vec<RichText>) -> String {
     }
     out
 }
+
+fn color_name(color: &IntermediaryColor) -> &'static str {
+    match color {
+        IntermediaryColor::Blue => "blue",
+        IntermediaryColor::Brown => "brown",
+        IntermediaryColor::Default => "default",
+        IntermediaryColor::Gray => "gray",
+        IntermediaryColor::Green => "green",
+        IntermediaryColor::Orange => "orange",
+        IntermediaryColor::Yellow => "yellow",
+        IntermediaryColor::Pink => "pink",
+        IntermediaryColor::Purple => "purple",
+        IntermediaryColor::Red => "red",
+    }
+}
+
+/// A `<time>` showing just the date part of an ISO 8601 timestamp.
+fn time(iso: &str) -> String {
+    let date = iso.split('T').next().unwrap_or(iso);
+    format!("<time datetime=\"{}\">{}</time>", escape(iso), escape(date))
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out += "&amp;",
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '"' => out += "&quot;",
+            '\'' => out += "&#39;",
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_blocks, RenderOptions};
+    use crate::intermediary::Block;
+
+    #[test]
+    fn unsupported_blocks_are_escaped() {
+        let html = from_blocks(
+            vec![Block::Unsupported {
+                ty: "wibble--><script>".to_string(),
+            }],
+            &RenderOptions::default(),
+        );
+        assert_eq!(
+            html,
+            "<div class=\"unsupported\" data-type=\"wibble--&gt;&lt;script&gt;\">Unsupported block: wibble--&gt;&lt;script&gt;</div>"
+        );
+    }
+}
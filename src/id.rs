@@ -0,0 +1,135 @@
+use reqwest::Url;
+
+use crate::error::{Error, Result};
+
+/// Turns whatever the user gave us into a dashed, lowercase page id.
+///
+/// Accepts
+/// - bare ids, with or without dashes: `428f22ca736843a992aa699a67787288`
+/// - page URLs on `notion.so` or `*.notion.site`, with or without the
+///   workspace slug, title slug, query and fragment:
+///   `https://www.notion.so/enymc/Sailing-the-High-Seas-428f22ca736843a992aa699a67787288?v=...#...`
+///
+/// When a URL opens a page on top of a database (`?p=<id>`) that page is used.
+pub fn parse_page_id(input: &str) -> Result<String> {
+    let input = input.trim();
+    let invalid = |why: &str| Error::InvalidId(format!("{:?} {}", input, why));
+
+    if let Some(id) = canonical(input) {
+        return Ok(id);
+    }
+
+    let looks_like_url = input.contains("://")
+        || input.starts_with("notion.so/")
+        || input.starts_with("www.notion.so/")
+        || input.contains(".notion.site/");
+    if !looks_like_url {
+        return Err(invalid("isn't a page id or Notion URL"));
+    }
+
+    let url = if input.contains("://") {
+        Url::parse(input)
+    } else {
+        Url::parse(&format!("https://{}", input))
+    }
+    .map_err(|_| invalid("isn't a valid URL"))?;
+
+    let host = url.host_str().unwrap_or_default();
+    let is_notion = host == "notion.so"
+        || host.ends_with(".notion.so")
+        || host == "notion.site"
+        || host.ends_with(".notion.site");
+    if !is_notion {
+        return Err(invalid("isn't a notion.so or notion.site URL"));
+    }
+
+    if let Some((_, peek)) = url.query_pairs().find(|(key, _)| key == "p") {
+        return canonical(&peek).ok_or_else(|| invalid("has an invalid page id in ?p="));
+    }
+
+    let segment = url
+        .path_segments()
+        .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+        .ok_or_else(|| invalid("doesn't point at a page"))?;
+
+    canonical(segment)
+        .or_else(|| {
+            // `Title-Of-The-Page-<id>`
+            let (slug, id) = segment.split_at(segment.len().checked_sub(32)?);
+            if slug.is_empty() || slug.ends_with('-') {
+                canonical(id)
+            } else {
+                None
+            }
+        })
+        .ok_or_else(|| invalid("doesn't end in a page id"))
+}
+
+/// `428f22ca736843a992aa699a67787288` or `428f22ca-7368-43a9-92aa-699a67787288`
+/// to the latter, anything else to `None`.
+fn canonical(id: &str) -> Option<String> {
+    let hex: String = match id.len() {
+        32 => id.to_string(),
+        36 if [8, 13, 18, 23].iter().all(|&i| id.as_bytes()[i] == b'-') => id.replace('-', ""),
+        _ => return None,
+    };
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let hex = hex.to_ascii_lowercase();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_page_id;
+
+    const ID: &str = "428f22ca-7368-43a9-92aa-699a67787288";
+
+    #[test]
+    fn accepted() {
+        for input in [
+            "428f22ca736843a992aa699a67787288",
+            "428f22ca-7368-43a9-92aa-699a67787288",
+            "428F22CA736843A992AA699A67787288",
+            "  428f22ca736843a992aa699a67787288\n",
+            "https://www.notion.so/enymc/Sailing-the-High-Seas-428f22ca736843a992aa699a67787288",
+            "www.notion.so/enymc/Sailing-the-High-Seas-428f22ca736843a992aa699a67787288",
+            "https://notion.so/428f22ca736843a992aa699a67787288",
+            "https://enymc.notion.site/428f22ca736843a992aa699a67787288",
+            "enymc.notion.site/Sailing-428f22ca736843a992aa699a67787288",
+            "https://www.notion.so/Sailing-428f22ca736843a992aa699a67787288?v=0123456789abcdef0123456789abcdef",
+            "https://www.notion.so/Sailing-428f22ca736843a992aa699a67787288#0123456789abcdef0123456789abcdef",
+            "https://www.notion.so/Sailing-428f22ca736843a992aa699a67787288/",
+            "https://www.notion.so/0123456789abcdef0123456789abcdef?v=1&p=428f22ca736843a992aa699a67787288",
+        ] {
+            assert_eq!(parse_page_id(input).ok().as_deref(), Some(ID), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejected() {
+        for input in [
+            "",
+            "428f22ca736843a992aa699a6778728",
+            "428f22ca736843a992aa699a6778728g",
+            "Sailing-the-High-Seas",
+            "https://example.com/428f22ca736843a992aa699a67787288",
+            "https://notion.so.example.com/428f22ca736843a992aa699a67787288",
+            "https://www.notion.so/",
+            "https://www.notion.so/enymc/",
+            "https://www.notion.so/Sailing428f22ca736843a992aa699a67787288",
+            "https://www.notion.so/Sailing-428f22ca736843a992aa699a67787288?p=nope",
+        ] {
+            assert!(parse_page_id(input).is_err(), "{:?}", input);
+        }
+    }
+}
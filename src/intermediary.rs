@@ -4,7 +4,11 @@ use log::warn;
 
 use crate::notion::Block as NotionBlock;
 use crate::notion::BlockData as NotionBlockData;
+use crate::notion::FileObject;
+use crate::notion::Icon as NotionIcon;
 use crate::notion::NotionColor;
+use crate::notion::Page as NotionPage;
+use crate::notion::Property as NotionProperty;
 use crate::notion::RichText as NotionRichText;
 
 pub fn parse_blocks(notion: Vec<NotionBlock>) -> Vec<Block> {
@@ -12,35 +16,29 @@ pub fn parse_blocks(notion: Vec<NotionBlock>) -> Vec<Block> {
     for block in notion {
         match block.block {
             // TODO: not ignore color?
-            NotionBlockData::Heading1 { rich_text, .. } => out.push(Block::Header {
-                rich_text: notion_to_text(rich_text),
-                size: 1,
-            }),
-            NotionBlockData::Heading2 { rich_text, .. } => out.push(Block::Header {
-                rich_text: notion_to_text(rich_text),
-                size: 2,
-            }),
-            NotionBlockData::Heading3 { rich_text, .. } => out.push(Block::Header {
-                rich_text: notion_to_text(rich_text),
-                size: 3,
-            }),
+            NotionBlockData::Heading1 {
+                rich_text,
+                is_toggleable,
+                children,
+            } => out.push(header(1, &block.id, rich_text, is_toggleable, children)),
+            NotionBlockData::Heading2 {
+                rich_text,
+                is_toggleable,
+                children,
+            } => out.push(header(2, &block.id, rich_text, is_toggleable, children)),
+            NotionBlockData::Heading3 {
+                rich_text,
+                is_toggleable,
+                children,
+            } => out.push(header(3, &block.id, rich_text, is_toggleable, children)),
             NotionBlockData::Quote {
                 rich_text,
                 children,
                 ..
-            } => {
-                if let Some(children) = children {
-                    out.push(Block::Quote {
-                        rich_text: notion_to_text(rich_text),
-                        children: Some(parse_blocks(children)),
-                    });
-                } else {
-                    out.push(Block::Quote {
-                        rich_text: notion_to_text(rich_text),
-                        children: None,
-                    });
-                }
-            }
+            } => out.push(Block::Quote {
+                rich_text: notion_to_text(rich_text),
+                children: children.map(parse_blocks),
+            }),
             NotionBlockData::Code {
                 rich_text,
                 language,
@@ -54,79 +52,325 @@ pub fn parse_blocks(notion: Vec<NotionBlock>) -> Vec<Block> {
                 checked,
                 children,
                 ..
-            } => {
-                if let Some(_) = children {
-                    out.push(Block::TodoList {
-                        items: vec![(
-                            checked,
-                            Block::Line {
-                                rich_text: notion_to_text(rich_text),
-                            },
-                        )],
-                    });
-                    warn!("Ignoring children of todo block");
-                } else {
-                    out.push(Block::TodoList {
-                        items: vec![(
-                            checked,
-                            Block::Line {
-                                rich_text: notion_to_text(rich_text),
-                            },
-                        )],
-                    });
-                }
-            }
+            } => out.push(Block::TodoList {
+                items: vec![TodoItem {
+                    checked,
+                    rich_text: notion_to_text(rich_text),
+                    children: parse_blocks(children.unwrap_or_default()),
+                }],
+            }),
             NotionBlockData::BulletedListItem {
                 rich_text,
                 children,
                 ..
-            } => {
-                if let Some(_) = children {
-                    out.push(Block::List {
-                        items: vec![Block::Line {
-                            rich_text: notion_to_text(rich_text),
-                        }],
-                    });
-                    warn!("Ignoring children of list block");
-                } else {
-                    out.push(Block::List {
-                        items: vec![Block::Line {
-                            rich_text: notion_to_text(rich_text),
-                        }],
-                    });
-                }
-            }
+            } => out.push(Block::List {
+                items: vec![ListItem {
+                    rich_text: notion_to_text(rich_text),
+                    children: parse_blocks(children.unwrap_or_default()),
+                }],
+            }),
             NotionBlockData::NumberedListItem {
                 rich_text,
+                list_start_index,
                 children,
                 ..
+            } => out.push(Block::NumberedList {
+                start: list_start_index,
+                items: vec![ListItem {
+                    rich_text: notion_to_text(rich_text),
+                    children: parse_blocks(children.unwrap_or_default()),
+                }],
+            }),
+            NotionBlockData::Divider {} => out.push(Block::Divider),
+            NotionBlockData::Paragraph {
+                rich_text,
+                children,
+                ..
+            } => out.push(Block::Line {
+                rich_text: notion_to_text(rich_text),
+                children: parse_blocks(children.unwrap_or_default()),
+            }),
+            NotionBlockData::Toggle {
+                rich_text,
+                children,
+            } => out.push(Block::Toggle {
+                rich_text: notion_to_text(rich_text),
+                children: parse_blocks(children.unwrap_or_default()),
+            }),
+            NotionBlockData::Callout {
+                rich_text,
+                icon,
+                color,
+                children,
+            } => out.push(Block::Callout {
+                rich_text: notion_to_text(rich_text),
+                icon: icon.and_then(notion_to_icon),
+                background: color.is_background(),
+                color: IntermediaryColor::ignoring_background(color),
+                children: parse_blocks(children.unwrap_or_default()),
+            }),
+            NotionBlockData::Table {
+                table_width,
+                has_column_header,
+                has_row_header,
+                children,
             } => {
-                if let Some(_) = children {
-                    out.push(Block::List {
-                        items: vec![Block::Line {
-                            rich_text: notion_to_text(rich_text),
-                        }],
-                    });
-                    warn!("Ignoring children of list block");
-                } else {
-                    out.push(Block::NumberedList {
-                        items: vec![Block::Line {
-                            rich_text: notion_to_text(rich_text),
-                        }],
-                    });
-                }
+                let rows = children
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|row| match row.block {
+                        NotionBlockData::TableRow { cells } => {
+                            let mut row: Vec<_> = cells.into_iter().map(notion_to_text).collect();
+                            row.resize_with(table_width.max(row.len()), Vec::new);
+                            Some(row)
+                        }
+                        _ => {
+                            warn!("Ignoring {} block in table", row.ty);
+                            None
+                        }
+                    })
+                    .collect();
+
+                out.push(Block::Table {
+                    has_column_header,
+                    has_row_header,
+                    rows,
+                });
             }
-            NotionBlockData::Divider => out.push(Block::Divider),
-            NotionBlockData::Paragraph { rich_text, .. } => out.push(Block::Line {
-                rich_text: notion_to_text(rich_text),
+            NotionBlockData::ColumnList { children } => {
+                let columns = children
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|column| match column.block {
+                        NotionBlockData::Column {
+                            width_ratio,
+                            children,
+                        } => Some(Column {
+                            width_ratio,
+                            children: parse_blocks(children.unwrap_or_default()),
+                        }),
+                        _ => {
+                            warn!("Ignoring {} block in column list", column.ty);
+                            None
+                        }
+                    })
+                    .collect();
+
+                out.push(Block::Columns { columns });
+            }
+            NotionBlockData::Image { caption, source } => out.push(Block::Image {
+                url: source.url().to_string(),
+                caption: notion_to_text(caption),
+            }),
+            NotionBlockData::Video {
+                caption,
+                name,
+                source,
+            } => out.push(media(MediaKind::Video, caption, name, source)),
+            NotionBlockData::Audio {
+                caption,
+                name,
+                source,
+            } => out.push(media(MediaKind::Audio, caption, name, source)),
+            NotionBlockData::Pdf {
+                caption,
+                name,
+                source,
+            } => out.push(media(MediaKind::Pdf, caption, name, source)),
+            NotionBlockData::File {
+                caption,
+                name,
+                source,
+            } => out.push(media(MediaKind::File, caption, name, source)),
+            NotionBlockData::Bookmark { caption, url } => out.push(Block::Bookmark {
+                url,
+                title: None,
+                description: None,
+                caption: notion_to_text(caption),
+            }),
+            NotionBlockData::LinkPreview { url } => out.push(Block::Bookmark {
+                url,
+                title: None,
+                description: None,
+                caption: Vec::new(),
             }),
-            _ => warn!("Can't find intermediary block type for {:?}", block.ty),
+            NotionBlockData::Embed { caption, url } => out.push(Block::Embed {
+                url,
+                caption: notion_to_text(caption),
+            }),
+            NotionBlockData::Equation { expression } => out.push(Block::Equation { expression }),
+            // synced blocks are just a container for content shared between pages
+            NotionBlockData::SyncedBlock { children } => {
+                out.extend(parse_blocks(children.unwrap_or_default()))
+            }
+            _ => {
+                warn!("Can't find intermediary block type for {:?}", block.ty);
+                out.push(Block::Unsupported { ty: block.ty });
+            }
         };
     }
 
     out
 }
 
+/// Calls `f` on every block in the tree, each one before its children.
+pub fn visit_mut(blocks: &mut [Block], f: &mut impl FnMut(&mut Block)) {
+    for block in blocks {
+        f(block);
+        match block {
+            Block::Header {
+                children: Some(children),
+                ..
+            }
+            | Block::Quote {
+                children: Some(children),
+                ..
+            }
+            | Block::Line { children, .. }
+            | Block::Toggle { children, .. }
+            | Block::Callout { children, .. } => visit_mut(children, f),
+            Block::List { items } | Block::NumberedList { items, .. } => {
+                for item in items {
+                    visit_mut(&mut item.children, f);
+                }
+            }
+            Block::TodoList { items } => {
+                for item in items {
+                    visit_mut(&mut item.children, f);
+                }
+            }
+            Block::Columns { columns } => {
+                for column in columns {
+                    visit_mut(&mut column.children, f);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn header(
+    size: usize,
+    id: &str,
+    rich_text: Vec<NotionRichText>,
+    is_toggleable: bool,
+    children: Option<Vec<NotionBlock>>,
+) -> Block {
+    Block::Header {
+        rich_text: notion_to_text(rich_text),
+        size,
+        // notion links to a block with its id, minus the dashes
+        anchor: Some(id.replace('-', "")).filter(|id| !id.is_empty()),
+        children: is_toggleable.then(|| parse_blocks(children.unwrap_or_default())),
+    }
+}
+
+fn media(
+    kind: MediaKind,
+    caption: Vec<NotionRichText>,
+    name: Option<String>,
+    source: FileObject,
+) -> Block {
+    let url = source.url().to_string();
+    // files linked from elsewhere don't have a name, so go by the URL
+    let name = name.filter(|name| !name.is_empty()).unwrap_or_else(|| {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let last = path.rsplit('/').find(|s| !s.is_empty()).unwrap_or(path);
+        last.to_string()
+    });
+
+    Block::Media {
+        kind,
+        url,
+        name,
+        caption: notion_to_text(caption),
+    }
+}
+
+pub fn parse_page(notion: NotionPage) -> Page {
+    let mut title = Vec::new();
+    let mut properties = Vec::new();
+
+    for (name, property) in notion.properties {
+        let value = match property {
+            NotionProperty::Title { title: text } => {
+                title = notion_to_text(text);
+                continue;
+            }
+            NotionProperty::RichText { rich_text } if !rich_text.is_empty() => {
+                PropertyValue::Text(notion_to_text(rich_text))
+            }
+            NotionProperty::Number {
+                number: Some(number),
+            } => PropertyValue::Text(vec![plain(number.to_string())]),
+            NotionProperty::Select {
+                select: Some(option),
+            }
+            | NotionProperty::Status {
+                status: Some(option),
+            } => PropertyValue::Tags(vec![option.name]),
+            NotionProperty::MultiSelect { multi_select } if !multi_select.is_empty() => {
+                PropertyValue::Tags(multi_select.into_iter().map(|o| o.name).collect())
+            }
+            NotionProperty::Date { date: Some(date) } => PropertyValue::Date {
+                start: date.start,
+                end: date.end,
+            },
+            NotionProperty::Checkbox { checkbox } => PropertyValue::Checkbox(checkbox),
+            NotionProperty::Url { url: Some(url) } => PropertyValue::Link {
+                href: url.clone(),
+                text: url,
+            },
+            NotionProperty::Email { email: Some(email) } => PropertyValue::Link {
+                href: format!("mailto:{}", email),
+                text: email,
+            },
+            NotionProperty::PhoneNumber {
+                phone_number: Some(phone),
+            } => PropertyValue::Link {
+                href: format!("tel:{}", phone),
+                text: phone,
+            },
+            NotionProperty::CreatedTime { created_time: time }
+            | NotionProperty::LastEditedTime {
+                last_edited_time: time,
+            } => PropertyValue::Date {
+                start: time,
+                end: None,
+            },
+            // empty, or something we can't show
+            _ => continue,
+        };
+        properties.push(Property { name, value });
+    }
+
+    Page {
+        title,
+        icon: notion.icon.and_then(notion_to_icon),
+        cover: notion.cover.map(|cover| cover.url().to_string()),
+        created: notion.created_time,
+        last_edited: notion.last_edited_time,
+        properties,
+    }
+}
+
+fn notion_to_icon(icon: NotionIcon) -> Option<Icon> {
+    match icon {
+        NotionIcon::Emoji { emoji } => Some(Icon::Emoji(emoji)),
+        NotionIcon::External { external: file } | NotionIcon::File { file } => {
+            Some(Icon::Image(file.url))
+        }
+        NotionIcon::Unsupported => None,
+    }
+}
+
+fn plain(text: String) -> RichText {
+    RichText {
+        plain_text: text,
+        ..Default::default()
+    }
+}
+
 fn notion_to_text(text: Vec<NotionRichText>) -> Vec<RichText> {
     let mut out = Vec::new();
     for t in text {
@@ -146,10 +390,15 @@ fn notion_to_text(text: Vec<NotionRichText>) -> Vec<RichText> {
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Block {
     Header {
         rich_text: Vec<RichText>,
         size: usize,
+        /// The `id` to give the heading so it can be linked to.
+        anchor: Option<String>,
+        /// The content of a toggleable heading, `None` if it isn't one.
+        children: Option<Vec<Block>>,
     },
     Divider,
     Quote {
@@ -160,20 +409,83 @@ pub enum Block {
         text: String,
         lang: String,
     },
-    //Image {
-    //    url: String,
-    //},
+    Image {
+        url: String,
+        caption: Vec<RichText>,
+    },
+    /// A video, audio clip, PDF or any other file.
+    Media {
+        kind: MediaKind,
+        url: String,
+        /// The file's name, for links to it.
+        name: String,
+        caption: Vec<RichText>,
+    },
+    /// A link shown as a card.
+    Bookmark {
+        url: String,
+        /// The linked page's title, if a [`MetadataProvider`] found it.
+        ///
+        /// [`MetadataProvider`]: crate::links::MetadataProvider
+        title: Option<String>,
+        description: Option<String>,
+        caption: Vec<RichText>,
+    },
+    /// Another site's page shown inline.
+    Embed {
+        url: String,
+        caption: Vec<RichText>,
+    },
+    /// Display math, as LaTeX.
+    Equation {
+        expression: String,
+    },
     List {
-        items: Vec<Block>,
+        items: Vec<ListItem>,
     },
     NumberedList {
-        items: Vec<Block>,
+        /// The number of the first item, if it doesn't just carry on from
+        /// the item before it (or start at 1).
+        start: Option<usize>,
+        items: Vec<ListItem>,
     },
     TodoList {
-        items: Vec<(bool, Block)>,
+        items: Vec<TodoItem>,
     },
     Line {
         rich_text: Vec<RichText>,
+        /// Blocks indented under this one.
+        children: Vec<Block>,
+    },
+    /// Content hidden behind a clickable summary.
+    Toggle {
+        rich_text: Vec<RichText>,
+        children: Vec<Block>,
+    },
+    /// A box set apart from the text around it, like a tip or warning.
+    Callout {
+        rich_text: Vec<RichText>,
+        icon: Option<Icon>,
+        color: IntermediaryColor,
+        /// Whether `color` is the background rather than the text.
+        background: bool,
+        children: Vec<Block>,
+    },
+    Table {
+        /// Whether the first row is a header.
+        has_column_header: bool,
+        /// Whether the first cell of each row is a header.
+        has_row_header: bool,
+        /// Rows of cells of text.
+        rows: Vec<Vec<Vec<RichText>>>,
+    },
+    /// Blocks laid out side by side.
+    Columns {
+        columns: Vec<Column>,
+    },
+    /// Stands in for a Notion block we can't convert.
+    Unsupported {
+        ty: String,
     },
     Empty,
 }
@@ -185,20 +497,102 @@ impl Display for Block {
             "{}",
             match self {
                 Block::Header { .. } => "Header",
-                Block::Divider { .. } => "Divider",
+                Block::Divider => "Divider",
                 Block::Quote { .. } => "Quote",
                 Block::CodeBlock { .. } => "CodeBlock",
-                //Block::Image { .. } => "Image",
+                Block::Image { .. } => "Image",
+                Block::Media { .. } => "Media",
+                Block::Bookmark { .. } => "Bookmark",
+                Block::Embed { .. } => "Embed",
+                Block::Equation { .. } => "Equation",
                 Block::List { .. } => "List",
                 Block::NumberedList { .. } => "NumberedList",
                 Block::TodoList { .. } => "TodoList",
                 Block::Line { .. } => "Line",
-                Block::Empty { .. } => "Empty",
+                Block::Toggle { .. } => "Toggle",
+                Block::Callout { .. } => "Callout",
+                Block::Table { .. } => "Table",
+                Block::Columns { .. } => "Columns",
+                Block::Unsupported { .. } => "Unsupported",
+                Block::Empty => "Empty",
             }
         )
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Video,
+    Audio,
+    Pdf,
+    /// Anything else, which can only be downloaded.
+    File,
+}
+
+/// An item of a bulleted or numbered list, along with anything nested under it.
+#[derive(Debug)]
+pub struct ListItem {
+    pub rich_text: Vec<RichText>,
+    pub children: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub struct TodoItem {
+    pub checked: bool,
+    pub rich_text: Vec<RichText>,
+    pub children: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub struct Column {
+    /// The share of the width this column takes up, `None` to split it
+    /// evenly.
+    pub width_ratio: Option<f64>,
+    pub children: Vec<Block>,
+}
+
+/// Everything about a page that isn't its content.
+#[derive(Debug)]
+pub struct Page {
+    pub title: Vec<RichText>,
+    pub icon: Option<Icon>,
+    /// URL of the cover image.
+    pub cover: Option<String>,
+    pub created: String,
+    pub last_edited: String,
+    /// Every property other than the title that has a value we can show.
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+pub enum Icon {
+    Emoji(String),
+    /// URL of the icon image.
+    Image(String),
+}
+
+#[derive(Debug)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+#[derive(Debug)]
+pub enum PropertyValue {
+    Text(Vec<RichText>),
+    Tags(Vec<String>),
+    /// ISO 8601 date or datetime, as Notion gives it.
+    Date {
+        start: String,
+        end: Option<String>,
+    },
+    Checkbox(bool),
+    Link {
+        href: String,
+        text: String,
+    },
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct RichText {
     pub plain_text: String,
@@ -242,3 +636,21 @@ impl From<NotionColor> for IntermediaryColor {
         }
     }
 }
+
+impl IntermediaryColor {
+    /// Like `from`, but `yellow_background` is yellow rather than default.
+    fn ignoring_background(value: NotionColor) -> Self {
+        match value {
+            NotionColor::BlueBackground => IntermediaryColor::Blue,
+            NotionColor::BrownBackground => IntermediaryColor::Brown,
+            NotionColor::GrayBackground => IntermediaryColor::Gray,
+            NotionColor::GreenBackground => IntermediaryColor::Green,
+            NotionColor::OrangeBackground => IntermediaryColor::Orange,
+            NotionColor::YellowBackground => IntermediaryColor::Yellow,
+            NotionColor::PinkBackground => IntermediaryColor::Pink,
+            NotionColor::PurpleBackground => IntermediaryColor::Purple,
+            NotionColor::RedBackground => IntermediaryColor::Red,
+            other => IntermediaryColor::from(other),
+        }
+    }
+}
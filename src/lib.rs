@@ -0,0 +1,95 @@
+//! Convert Notion pages to HTML.
+//!
+//! Conversion happens in three steps, each of which can be used on its own:
+//!
+//! 1. [`fetch_page`] (or a [`Fetcher`] for more control) pulls the block tree
+//!    of a page from the Notion API as [`notion::Block`]s. [`load_page`] reads
+//!    the same thing from a saved response instead.
+//! 2. [`parse`] turns those into the simpler [`intermediary::Block`] tree.
+//! 3. [`render`] turns the intermediary tree into an HTML fragment.
+//!
+//! The page's title, icon, cover and properties come separately from
+//! [`fetch_page_info`] and [`intermediary::parse_page`], and [`render_page`]
+//! puts them together with the content as a full HTML document.
+//!
+//! Files Notion hosts are only linked for an hour, so [`Assets`] can download
+//! them and rewrite the intermediary tree to point at the local copies before
+//! it's rendered.
+//!
+//! Bookmarks render as bare cards with just their URL, unless
+//! [`links::enrich_blocks`] is given a [`MetadataProvider`] (like
+//! [`HttpMetadata`]) to look up the titles of the pages they link to.
+//!
+//! [`convert`] does all of that (bar downloading and looking up links) at once.
+
+pub mod assets;
+pub mod embed;
+pub mod error;
+pub mod fetch;
+pub mod html;
+pub mod id;
+pub mod intermediary;
+pub mod links;
+pub mod math;
+pub mod notion;
+pub mod retry;
+
+pub use assets::Assets;
+pub use error::{Error, Result};
+pub use fetch::Fetcher;
+pub use html::RenderOptions;
+pub use id::parse_page_id;
+pub use links::{HttpMetadata, MetadataProvider};
+pub use reqwest::StatusCode;
+
+/// Fetches the full block tree of a page. `page` can be an id or a Notion URL,
+/// see [`parse_page_id`].
+pub fn fetch_page(token: &str, page: &str) -> Result<Vec<notion::Block>> {
+    let page_id = parse_page_id(page)?;
+    Fetcher::new(token.to_string())?.block_tree(&page_id)
+}
+
+/// Fetches the title, icon, cover and properties of a page.
+pub fn fetch_page_info(token: &str, page: &str) -> Result<notion::Page> {
+    let page_id = parse_page_id(page)?;
+    Fetcher::new(token.to_string())?.page(&page_id)
+}
+
+/// Reads the blocks of a page from a saved block children response, the JSON
+/// that `GET /v1/blocks/{id}/children` returns. Nested children can be given
+/// inline under each block's type object, e.g.
+/// `"toggle": {"rich_text": [...], "children": [...]}`.
+pub fn load_page(reader: impl std::io::Read) -> Result<Vec<notion::Block>> {
+    let data: notion::PageResponse = serde_json::from_reader(reader).map_err(Error::from_json)?;
+    Ok(data.results)
+}
+
+/// Parses Notion blocks into the intermediary format.
+pub fn parse(blocks: Vec<notion::Block>) -> Vec<intermediary::Block> {
+    intermediary::parse_blocks(blocks)
+}
+
+/// Renders intermediary blocks to an HTML fragment.
+pub fn render(blocks: Vec<intermediary::Block>, options: &RenderOptions) -> String {
+    html::from_blocks(blocks, options)
+}
+
+/// Renders a page's metadata and content to a full HTML document.
+pub fn render_page(
+    page: intermediary::Page,
+    blocks: Vec<intermediary::Block>,
+    options: &RenderOptions,
+) -> String {
+    html::from_page(page, blocks, options)
+}
+
+/// Fetches a page and renders it to a full HTML document.
+pub fn convert(token: &str, page: &str) -> Result<String> {
+    let page_id = parse_page_id(page)?;
+    let fetcher = Fetcher::new(token.to_string())?;
+
+    let info = intermediary::parse_page(fetcher.page(&page_id)?);
+    let blocks = parse(fetcher.block_tree(&page_id)?);
+
+    Ok(render_page(info, blocks, &RenderOptions::default()))
+}
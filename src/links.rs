@@ -0,0 +1,284 @@
+use std::{collections::HashMap, io::Read};
+
+use log::{debug, warn};
+use reqwest::header::CONTENT_TYPE;
+
+use crate::error::{Error, Result};
+use crate::intermediary::{visit_mut, Block};
+use crate::retry::RetryClient;
+
+/// How much of a linked page we read looking for its title. It's in the
+/// `<head>`, so there's no need to download the whole thing.
+const MAX_HEAD_BYTES: u64 = 256 * 1024;
+
+/// What we know about a linked page beyond its URL.
+#[derive(Debug, Default, Clone)]
+pub struct LinkMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Looks up the title and description of pages that bookmarks link to.
+///
+/// Nothing is looked up unless a provider is passed to [`enrich_blocks`], so
+/// conversion works offline by default.
+pub trait MetadataProvider {
+    fn metadata(&mut self, url: &str) -> Result<LinkMetadata>;
+}
+
+/// Fills in the title and description of every bookmark in the tree. A link
+/// that can't be looked up is left as a bare card.
+pub fn enrich_blocks(blocks: &mut [Block], provider: &mut dyn MetadataProvider) {
+    let mut done: HashMap<String, LinkMetadata> = HashMap::new();
+
+    visit_mut(blocks, &mut |block| {
+        if let Block::Bookmark {
+            url,
+            title,
+            description,
+            ..
+        } = block
+        {
+            let metadata = match done.get(url.as_str()) {
+                Some(metadata) => metadata.clone(),
+                None => {
+                    let metadata = provider.metadata(url).unwrap_or_else(|err| {
+                        warn!("Couldn't look up {}: {}", url, err);
+                        LinkMetadata::default()
+                    });
+                    done.insert(url.clone(), metadata.clone());
+                    metadata
+                }
+            };
+            *title = metadata.title;
+            *description = metadata.description;
+        }
+    });
+}
+
+/// Fetches linked pages and reads their `<title>` and OpenGraph tags.
+pub struct HttpMetadata {
+    client: RetryClient,
+}
+
+impl HttpMetadata {
+    pub fn new() -> Result<Self> {
+        let client = RetryClient::with_default_client()?;
+
+        Ok(Self {
+            client: client.with_max_attempts(2),
+        })
+    }
+}
+
+impl MetadataProvider for HttpMetadata {
+    fn metadata(&mut self, url: &str) -> Result<LinkMetadata> {
+        debug!("Looking up {}", url);
+        let res = self.client.send(self.client.get(url))?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Error::Http {
+                status,
+                code: None,
+                message: None,
+            });
+        }
+
+        let is_html = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if !is_html {
+            return Ok(LinkMetadata::default());
+        }
+
+        let mut head = Vec::new();
+        res.take(MAX_HEAD_BYTES).read_to_end(&mut head)?;
+        Ok(parse_head(&String::from_utf8_lossy(&head)))
+    }
+}
+
+/// Picks the title and description out of an HTML page, preferring the
+/// OpenGraph ones since they tend to be written for exactly this.
+fn parse_head(html: &str) -> LinkMetadata {
+    let mut og_title = None;
+    let mut title = None;
+    let mut og_description = None;
+    let mut description = None;
+
+    let lower = html.to_ascii_lowercase();
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find('<').map(|i| rest + i) {
+        let Some(end) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        let tag = &html[start + 1..end];
+        rest = end + 1;
+
+        let name = tag
+            .split(|c: char| c.is_ascii_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "title" if title.is_none() => {
+                if let Some(close) = lower[rest..].find("</title") {
+                    title = Some(unescape(&html[rest..rest + close]));
+                }
+            }
+            "meta" => {
+                let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+                let content = attribute(tag, "content").map(|c| unescape(&c));
+                match key.as_deref().map(str::to_ascii_lowercase).as_deref() {
+                    Some("og:title") => og_title = og_title.or(content),
+                    Some("og:description") => og_description = og_description.or(content),
+                    Some("description") => description = description.or(content),
+                    _ => {}
+                }
+            }
+            "/head" | "body" => break,
+            _ => {}
+        }
+    }
+
+    let tidy = |text: Option<String>| {
+        text.map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|t| !t.is_empty())
+    };
+    LinkMetadata {
+        title: tidy(og_title).or_else(|| tidy(title)),
+        description: tidy(og_description).or_else(|| tidy(description)),
+    }
+}
+
+/// The value of `name="..."` (or `'...'`) inside a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(i) = lower[from..].find(name).map(|i| from + i) {
+        from = i + name.len();
+        // make sure we matched a whole attribute name, not part of one
+        let before = lower[..i].chars().next_back();
+        if !before.is_some_and(|c| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let after = lower[from..].trim_start();
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let offset = tag.len() - value.len();
+        let quote = value.chars().next()?;
+        return if quote == '"' || quote == '\'' {
+            let value = &tag[offset + 1..];
+            value.find(quote).map(|end| value[..end].to_string())
+        } else {
+            let value = &tag[offset..];
+            let end = value.find(|c: char| c.is_ascii_whitespace() || c == '/');
+            Some(value[..end.unwrap_or(value.len())].to_string())
+        };
+    }
+    None
+}
+
+/// Undoes the HTML escaping you'd find in a title, leaving anything more
+/// exotic alone.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out += &rest[..i];
+        rest = &rest[i..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let decoded = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some("nbsp") => Some(' '),
+            Some(e) if e.starts_with("#x") || e.starts_with("#X") => {
+                u32::from_str_radix(&e[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            Some(e) if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match (decoded, entity) {
+            (Some(c), Some(e)) => {
+                out.push(c);
+                rest = &rest[e.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attribute, parse_head, unescape};
+
+    #[test]
+    fn opengraph_wins_over_title() {
+        let metadata = parse_head(
+            "<html><head><title>Page | Site</title>\
+             <meta property=\"og:title\" content=\"Page\">\
+             <meta name=\"description\" content=\"Plain\">\
+             <meta property=\"og:description\" content=\"Open  graph\n\">",
+        );
+        assert_eq!(metadata.title.as_deref(), Some("Page"));
+        assert_eq!(metadata.description.as_deref(), Some("Open graph"));
+
+        let metadata =
+            parse_head("<TITLE> Just the\n title </TITLE><META NAME=description CONTENT='Plain'>");
+        assert_eq!(metadata.title.as_deref(), Some("Just the title"));
+        assert_eq!(metadata.description.as_deref(), Some("Plain"));
+    }
+
+    #[test]
+    fn stops_at_the_body() {
+        let metadata = parse_head(
+            "<head><meta name=\"og:title\" content=\"Head\"></head>\
+             <body><title>Body</title><meta property=\"og:description\" content=\"Body\">",
+        );
+        assert_eq!(metadata.title.as_deref(), Some("Head"));
+        assert_eq!(metadata.description, None);
+    }
+
+    #[test]
+    fn attributes() {
+        let tag = "meta data-name=\"no\" name='description' content=\"a 'b'\"";
+        assert_eq!(attribute(tag, "name").as_deref(), Some("description"));
+        assert_eq!(attribute(tag, "content").as_deref(), Some("a 'b'"));
+        assert_eq!(attribute("meta data-name=\"no\"", "name"), None);
+        assert_eq!(
+            attribute("meta name = og:title content=x/", "name").as_deref(),
+            Some("og:title")
+        );
+        assert_eq!(
+            attribute("meta name = og:title content=x/", "content").as_deref(),
+            Some("x")
+        );
+        assert_eq!(attribute("meta content", "content"), None);
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            unescape("Tom &amp; Jerry&#39;s &lt;b&gt;"),
+            "Tom & Jerry's <b>"
+        );
+        assert_eq!(unescape("&#x1F600; &#X41;&#66;"), "😀 AB");
+        assert_eq!(
+            unescape("fish & chips; &bogus; &"),
+            "fish & chips; &bogus; &"
+        );
+        assert_eq!(unescape("&#xD800;"), "&#xD800;");
+    }
+}
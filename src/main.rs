@@ -1,35 +1,62 @@
-use log::{debug, error, info};
-use notion::PageResponse;
+use std::{fs::File, io::BufReader, process::ExitCode};
+
+use args::Args;
+use log::{debug, error, info, warn};
+use notion2html::{
+    fetch::Fixtures,
+    notion::{Block, Page},
+    Assets, Error, Fetcher, HttpMetadata, RenderOptions, StatusCode,
+};
 use owo_colors::OwoColorize;
-use reqwest::{blocking::ClientBuilder, StatusCode};
 use simplelog::TermLogger;
 
-mod html;
-mod intermediary;
-mod notion;
+mod args;
 
-//#[derive(Debug, Deserialize)]
-//pub enum Block {}
+// exit codes, one per way things can go wrong
+const EXIT_USAGE: u8 = 2;
+const EXIT_TRANSPORT: u8 = 3;
+const EXIT_HTTP: u8 = 4;
+const EXIT_JSON: u8 = 5;
+const EXIT_UNSUPPORTED: u8 = 6;
+const EXIT_IO: u8 = 7;
 
-fn main() {
-    let executable = std::env::args().nth(0).unwrap_or("notion2html".to_string());
-    let arg = std::env::args().nth(1);
-    let page = match arg {
-        Some(ref arg) => arg,
-        _ => {
+fn main() -> ExitCode {
+    let executable = std::env::args().next().unwrap_or("notion2html".to_string());
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{} {}\n", "Error:".bright_red().bold(), err.red());
             help();
-            return;
+            return ExitCode::from(EXIT_USAGE);
         }
     };
+    if args.help {
+        help();
+        return ExitCode::SUCCESS;
+    }
+    if args.page.is_none() && args.file.is_none() {
+        help();
+        return ExitCode::from(EXIT_USAGE);
+    }
 
-    let token = match std::env::var("NOTION_TOKEN") {
-        Ok(token) => token,
-        _ => {
-            no_auth();
-            return;
+    let page = match args.page.as_deref().map(notion2html::parse_page_id) {
+        Some(Ok(page)) => Some(page),
+        Some(Err(err)) => {
+            eprintln!("{} {}\n", "Error:".bright_red().bold(), err.red());
+            help();
+            return ExitCode::from(EXIT_USAGE);
         }
+        None => None,
     };
 
+    // only needed when we're actually talking to notion
+    let token = std::env::var("NOTION_TOKEN");
+    let offline = args.file.is_some() || args.replay.is_some();
+    if !offline && token.is_err() {
+        no_auth();
+        return ExitCode::from(EXIT_USAGE);
+    }
+
     TermLogger::init(
         if &executable == "notion2html" {
             simplelog::LevelFilter::Info
@@ -42,68 +69,142 @@ fn main() {
     )
     .unwrap();
 
+    // a saved response only has the blocks, not the page they're on
+    let results = match (&args.file, &page) {
+        (Some(file), _) => load(file).map(|results| (None, results)),
+        (None, Some(page)) => fetch(page, token.unwrap_or_default(), &args)
+            .map(|(info, results)| (Some(info), results)),
+        _ => unreachable!("checked above"),
+    };
+    let (info, results) = match results {
+        Ok(results) => results,
+        Err(err) => return fail(err),
+    };
+
+    debug!("Parsing blocks to intermediary...");
+    let mut blocks = notion2html::parse(results);
+    let mut info = info.map(notion2html::intermediary::parse_page);
+
+    if let Some(dir) = &args.assets {
+        info!("Downloading files into {}...", dir);
+        let mut assets = match Assets::new(dir) {
+            Ok(assets) => assets,
+            Err(err) => return fail(err),
+        };
+        if let Some(prefix) = &args.assets_url {
+            assets = assets.with_url_prefix(prefix);
+        }
+        assets.localize_blocks(&mut blocks);
+        if let Some(info) = &mut info {
+            assets.localize_page(info);
+        }
+    }
+
+    if args.link_titles {
+        info!("Looking up bookmarked pages...");
+        match HttpMetadata::new() {
+            Ok(mut provider) => notion2html::links::enrich_blocks(&mut blocks, &mut provider),
+            Err(err) => return fail(err),
+        }
+    }
+
+    debug!("Converting from intermediary format to HTML...");
+    let options = RenderOptions {
+        expand_toggles: args.expand_toggles,
+        raw_math: args.raw_math,
+        ..Default::default()
+    };
+    match info {
+        Some(info) => println!("{}", notion2html::render_page(info, blocks, &options)),
+        None => println!("{}", notion2html::render(blocks, &options)),
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn fetch(page: &str, token: String, args: &Args) -> notion2html::Result<(Page, Vec<Block>)> {
     info!("Fetching page {}...", page);
 
-    let client = ClientBuilder::new()
-        .user_agent(concat!(
-            env!("CARGO_PKG_NAME"),
-            "/",
-            env!("CARGO_PKG_VERSION"),
-        ))
-        .build()
-        .expect("Failed to create reqwest client");
-
-    debug!("Created reqwest client");
-
-    let req = client
-        .get(&format!(
-            "https://api.notion.com/v1/blocks/{}/children?page_size=100",
-            page
-        ))
-        .bearer_auth(token)
-        .header("Notion-Version", "2022-06-28")
-        .send()
-        .expect("Failed to fetch page");
-
-    debug!("Page fetched with status {}", req.status());
-
-    if !req.status().is_success() {
-        let status = req.status();
-        match status {
-            StatusCode::NOT_FOUND => {
-                error!(
-                    "Page not found! Have you added the integration to the page you want to see?"
-                );
-                return;
-            }
-            StatusCode::UNAUTHORIZED => {
-                error!("Unauthorized! Check your auth token. ");
-                return;
-            }
-            StatusCode::TOO_MANY_REQUESTS => {
-                error!("Too many requests! Please wait a bit before trying again.");
-                return;
-            }
-            _ => {
-                error!(
-                    "Failed to fetch page! Errored with {} {}. ",
-                    status,
-                    status.canonical_reason().unwrap_or("Unknown error")
-                );
-                return;
+    let mut fetcher = Fetcher::new(token)?;
+    if let Some(dir) = &args.record {
+        fetcher = fetcher.with_fixtures(Fixtures::Record(dir.into()));
+    }
+    if let Some(dir) = &args.replay {
+        fetcher = fetcher.with_fixtures(Fixtures::Replay(dir.into()));
+    }
+    // flags win over environment variables
+    match args.max_attempts {
+        Some(attempts) => fetcher = fetcher.with_max_attempts(attempts),
+        None => {
+            if let Ok(attempts) = std::env::var("NOTION_MAX_ATTEMPTS") {
+                match attempts.parse() {
+                    Ok(attempts) => fetcher = fetcher.with_max_attempts(attempts),
+                    Err(_) => warn!("Ignoring invalid NOTION_MAX_ATTEMPTS {:?}", attempts),
+                }
             }
         }
     }
+    if let Some(url) = args
+        .api_url
+        .clone()
+        .or(std::env::var("NOTION_API_URL").ok())
+    {
+        debug!("Using API at {}", url);
+        fetcher = fetcher.with_base_url(&url);
+    }
+    if let Some(version) = args
+        .notion_version
+        .clone()
+        .or(std::env::var("NOTION_VERSION").ok())
+    {
+        debug!("Using Notion-Version {}", version);
+        fetcher = fetcher.with_notion_version(&version);
+    }
 
-    let data: PageResponse = req.json().expect("Failed to parse JSON");
-
+    let info = fetcher.page(page)?;
+    let results = fetcher.block_tree(page)?;
     info!("Fetch successfully!");
 
-    debug!("Parsing blocks to intermediary...");
-    let blocks = intermediary::parse_blocks(data.results);
+    Ok((info, results))
+}
 
-    debug!("Converting from intermediary format to HTML...");
-    println!("{}", html::from_blocks(blocks, false));
+fn load(file: &str) -> notion2html::Result<Vec<Block>> {
+    if file == "-" {
+        info!("Reading page from stdin...");
+        notion2html::load_page(std::io::stdin().lock())
+    } else {
+        info!("Reading page from {}...", file);
+        notion2html::load_page(BufReader::new(File::open(file)?))
+    }
+}
+
+fn fail(err: Error) -> ExitCode {
+    match &err {
+        Error::Http {
+            status: StatusCode::NOT_FOUND,
+            ..
+        } => error!("Page not found! Have you added the integration to the page you want to see?"),
+        Error::Http {
+            status: StatusCode::UNAUTHORIZED,
+            ..
+        } => error!("Unauthorized! Check your auth token. "),
+        Error::Http {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            ..
+        } => error!("Still rate limited after retrying! Please wait a bit before trying again."),
+        Error::Io(_) => error!("Failed to read or write files! {}", err),
+        _ => error!("Failed to get page! {}", err),
+    }
+    debug!("{:?}", err);
+
+    ExitCode::from(match err {
+        Error::Transport(_) => EXIT_TRANSPORT,
+        Error::Http { .. } => EXIT_HTTP,
+        Error::Json(_) => EXIT_JSON,
+        Error::Unsupported(_) => EXIT_UNSUPPORTED,
+        Error::Io(_) => EXIT_IO,
+        Error::InvalidId(_) => EXIT_USAGE,
+    })
 }
 
 fn no_auth() {
@@ -125,6 +226,71 @@ fn help() {
         "{} {} {}",
         "Usage:".bright_green().bold(),
         "notion2html".bright_cyan(),
-        "<page_id>".cyan()
+        "<page_id|url>".cyan()
+    );
+    eprintln!(
+        "       {} {} {}",
+        "notion2html".bright_cyan(),
+        "--file".bright_cyan(),
+        "<response.json|->".cyan()
     );
+
+    eprintln!("\n{}", "Options:".bright_green().bold());
+    option(
+        "-f, --file <path>",
+        "convert a saved block children response (or stdin) without a token",
+    );
+    option(
+        "--expand-toggles",
+        "render toggles open instead of collapsed",
+    );
+    option(
+        "--raw-math",
+        "keep equations as LaTeX in \\[...\\] instead of MathML",
+    );
+    option(
+        "--link-titles",
+        "fetch bookmarked pages to show their titles on link cards",
+    );
+    option(
+        "--assets <dir>",
+        "download images and files into a directory and link to those",
+    );
+    option(
+        "--assets-url <url>",
+        "what to link downloaded files with (defaults to the directory's name)",
+    );
+    option(
+        "--record <dir>",
+        "save every Notion API response into a fixture directory",
+    );
+    option(
+        "--replay <dir>",
+        "use responses from a fixture directory instead of Notion",
+    );
+    option(
+        "--api-url <url>",
+        &format!(
+            "talk to another API, e.g. a mock server ({})",
+            "NOTION_API_URL".bold()
+        ),
+    );
+    option(
+        "--notion-version <v>",
+        &format!(
+            "Notion-Version header to send ({})",
+            "NOTION_VERSION".bold()
+        ),
+    );
+    option(
+        "--max-attempts <n>",
+        &format!(
+            "how many times to try each request ({})",
+            "NOTION_MAX_ATTEMPTS".bold()
+        ),
+    );
+}
+
+fn option(flag: &str, description: &str) {
+    eprintln!("  {}  {}", format!("{:<20}", flag).cyan(), description);
 }
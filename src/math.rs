@@ -0,0 +1,1191 @@
+//! LaTeX to MathML, so equations show up without a JavaScript renderer.
+//!
+//! This covers the LaTeX people actually write in Notion equations (which
+//! Notion renders with KaTeX): fractions, roots, scripts, big operators,
+//! `\left`/`\right`, accents, fonts, text, spacing and the usual matrix and
+//! alignment environments. Anything it doesn't know is shown in an
+//! `<merror>` rather than dropped.
+
+use crate::html::escape;
+
+/// Converts a LaTeX expression (without `$`s or `\[`) to a display-mode
+/// `<math>` element, with the original kept as an annotation.
+pub fn to_mathml(tex: &str) -> String {
+    let mut parser = Parser::new(tex);
+
+    let mut content = String::new();
+    loop {
+        let rows = parser.table();
+        content += &if rows.len() == 1 && rows[0].len() == 1 {
+            rows[0].concat()
+        } else {
+            // lines split with `\\` outside an environment
+            mtable(rows, &[Align::Center])
+        };
+        if parser.peek().is_none() {
+            break;
+        }
+        // something closed that was never opened, like a stray `}` or
+        // `\right)`
+        let stray = parser.stray();
+        content += &merror(&stray);
+    }
+
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><semantics><mrow>{}</mrow><annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        content,
+        escape(tex)
+    )
+}
+
+/// One piece of the output, along with whether scripts on it go above and
+/// below (like `\sum`) rather than to the side.
+struct Atom {
+    mathml: String,
+    limits: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Self {
+        Atom {
+            mathml,
+            limits: false,
+        }
+    }
+
+    fn limits(mathml: String) -> Self {
+        Atom {
+            mathml,
+            limits: true,
+        }
+    }
+}
+
+/// `\mathbb` and friends.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Font {
+    Upright,
+    Bold,
+    BoldItalic,
+    Script,
+    Fraktur,
+    DoubleStruck,
+    SansSerif,
+    Monospace,
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    font: Option<Font>,
+}
+
+impl Parser {
+    fn new(tex: &str) -> Self {
+        Parser {
+            chars: tex.chars().collect(),
+            pos: 0,
+            font: None,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(&c) = self.chars.get(self.pos) {
+            if c.is_whitespace() {
+                self.pos += 1;
+            } else if c == '%' {
+                // a comment, up to the end of the line
+                while self.chars.get(self.pos).is_some_and(|&c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The name of the command coming up, without taking it.
+    fn peek_command(&mut self) -> Option<String> {
+        if self.peek() != Some('\\') {
+            return None;
+        }
+        let start = self.pos + 1;
+        let mut end = start;
+        while self.chars.get(end).is_some_and(|c| c.is_ascii_alphabetic()) {
+            end += 1;
+        }
+        if end == start {
+            // a command made of one symbol, like `\,` or `\\`
+            end = (start + 1).min(self.chars.len());
+        }
+        Some(self.chars[start..end].iter().collect())
+    }
+
+    fn command(&mut self) -> Option<String> {
+        let name = self.peek_command()?;
+        self.pos += 1 + name.chars().count();
+        Some(name)
+    }
+
+    fn eat_command(&mut self, name: &str) -> bool {
+        if self.peek_command().as_deref() == Some(name) {
+            self.command();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the current row of a table (or group) ends here.
+    fn at_row_end(&mut self) -> bool {
+        match self.peek() {
+            None | Some('}') | Some('&') => true,
+            Some('\\') => matches!(
+                self.peek_command().as_deref(),
+                Some("\\" | "right" | "middle" | "end" | "cr")
+            ),
+            _ => false,
+        }
+    }
+
+    /// Elements up to the end of the current cell.
+    fn row(&mut self) -> Vec<String> {
+        let mut out = Vec::new();
+        while !self.at_row_end() {
+            if self.eat_command("color") {
+                // colours everything after it in the group
+                let color = self.raw_arg();
+                let rest = self.row().concat();
+                out.push(format!(
+                    "<mstyle mathcolor=\"{}\">{}</mstyle>",
+                    escape(&color),
+                    rest
+                ));
+                break;
+            }
+            out.push(self.scripted());
+        }
+        out
+    }
+
+    /// Rows of cells, split on `&` and `\\`.
+    fn table(&mut self) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        loop {
+            row.push(self.row().concat());
+            if self.eat('&') {
+                continue;
+            }
+            if self.eat_command("\\") || self.eat_command("cr") {
+                // `\\[2pt]` asks for extra space, which we don't bother with
+                if self.peek() == Some('[') {
+                    self.bracketed();
+                }
+                rows.push(std::mem::take(&mut row));
+                continue;
+            }
+            break;
+        }
+        // a `\\` at the very end doesn't start another row
+        if !(row.len() == 1 && row[0].is_empty() && !rows.is_empty()) {
+            rows.push(row);
+        }
+        rows
+    }
+
+    /// Whatever stopped [`Parser::table`] at the top level, taken so parsing
+    /// can carry on after it.
+    fn stray(&mut self) -> String {
+        match self.command() {
+            Some(name) => {
+                if name == "right" || name == "middle" {
+                    self.delimiter();
+                } else if name == "end" {
+                    self.raw_arg();
+                }
+                format!("\\{}", name)
+            }
+            None => {
+                self.pos += 1;
+                self.chars[self.pos - 1].to_string()
+            }
+        }
+    }
+
+    /// An atom followed by any sub- and superscripts.
+    fn scripted(&mut self) -> String {
+        let mut base = self.atom();
+
+        let mut sub = None;
+        // primes and a superscript can both go up there, like `f'^2`
+        let mut sup = Vec::new();
+        loop {
+            if self.eat_command("limits") {
+                base.limits = true;
+            } else if self.eat_command("nolimits") {
+                base.limits = false;
+            } else if self.eat('\'') {
+                sup.push(mo("′"));
+            } else if self.eat('^') {
+                sup.push(self.arg());
+            } else if self.eat('_') {
+                sub = Some(self.arg());
+            } else {
+                break;
+            }
+        }
+        let sup = match sup.len() {
+            0 => None,
+            1 => sup.pop(),
+            _ => Some(format!("<mrow>{}</mrow>", sup.concat())),
+        };
+
+        let (under, over, both) = if base.limits {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        match (sub, sup) {
+            (None, None) => base.mathml,
+            (Some(sub), None) => format!("<{0}>{1}{2}</{0}>", under, base.mathml, sub),
+            (None, Some(sup)) => format!("<{0}>{1}{2}</{0}>", over, base.mathml, sup),
+            (Some(sub), Some(sup)) => {
+                format!("<{0}>{1}{2}{3}</{0}>", both, base.mathml, sub, sup)
+            }
+        }
+    }
+
+    /// The argument of a command or script: a group, a command or a single
+    /// character (so `x^23` is `x²3`, like in TeX).
+    fn arg(&mut self) -> String {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                self.pos += 1;
+                self.number(&c.to_string())
+            }
+            _ => self.atom().mathml,
+        }
+    }
+
+    /// An argument taken as-is, for things like `\text{...}` and
+    /// `\begin{...}`.
+    fn raw_arg(&mut self) -> String {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let start = self.pos;
+                let mut depth = 0;
+                while let Some(&c) = self.chars.get(self.pos) {
+                    match c {
+                        '\\' => self.pos += 1,
+                        '{' => depth += 1,
+                        '}' if depth == 0 => break,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                let end = self.pos.min(self.chars.len());
+                self.pos = (self.pos + 1).min(self.chars.len());
+                self.chars[start..end].iter().collect()
+            }
+            Some(c) => {
+                self.pos += 1;
+                c.to_string()
+            }
+            None => String::new(),
+        }
+    }
+
+    /// What's inside `[...]`, if that comes next.
+    fn bracketed(&mut self) -> Option<String> {
+        if !self.eat('[') {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(&c) = self.chars.get(self.pos) {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ']' if depth == 0 => break,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        let inner = self.chars[start..self.pos].iter().collect();
+        self.pos = (self.pos + 1).min(self.chars.len());
+        Some(inner)
+    }
+
+    /// Parses a piece of LaTeX that was taken out of the input, like the
+    /// index of a root, in the current font.
+    fn sub_expression(&self, tex: &str) -> String {
+        let mut parser = Parser::new(tex);
+        parser.font = self.font;
+        format!("<mrow>{}</mrow>", parser.row().concat())
+    }
+
+    /// `arg` parsed with `font` in effect.
+    fn with_font(&mut self, font: Option<Font>) -> String {
+        let outer = std::mem::replace(&mut self.font, font);
+        let out = self.arg();
+        self.font = outer;
+        out
+    }
+
+    fn number(&mut self, start: &str) -> String {
+        let digits: String = start.chars().map(|c| styled(c, self.font)).collect();
+        format!("<mn>{}</mn>", escape(&digits))
+    }
+
+    fn identifier(&self, c: char) -> String {
+        match self.font {
+            Some(Font::Upright) => {
+                format!("<mi mathvariant=\"normal\">{}</mi>", escape(&c.to_string()))
+            }
+            font => mi(&styled(c, font).to_string()),
+        }
+    }
+
+    fn atom(&mut self) -> Atom {
+        let Some(c) = self.peek() else {
+            return Atom::new("<mrow></mrow>".to_string());
+        };
+
+        match c {
+            '{' => {
+                self.pos += 1;
+                let inner = self.row().concat();
+                self.eat('}');
+                Atom::new(format!("<mrow>{}</mrow>", inner))
+            }
+            '\\' => self.control(),
+            // scripts with nothing to attach to
+            '^' | '_' => Atom::new("<mrow></mrow>".to_string()),
+            '0'..='9' | '.' => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                if number == "." {
+                    return Atom::new(mo("."));
+                }
+                Atom::new(self.number(&number))
+            }
+            c if c.is_alphabetic() => {
+                self.pos += 1;
+                Atom::new(self.identifier(c))
+            }
+            '~' => {
+                self.pos += 1;
+                Atom::new("<mtext>\u{a0}</mtext>".to_string())
+            }
+            '\'' => {
+                self.pos += 1;
+                Atom::new(mo("′"))
+            }
+            '-' => {
+                self.pos += 1;
+                Atom::new(mo("−"))
+            }
+            '*' => {
+                self.pos += 1;
+                Atom::new(mo("∗"))
+            }
+            _ => {
+                self.pos += 1;
+                Atom::new(mo(&c.to_string()))
+            }
+        }
+    }
+
+    /// Everything that starts with a backslash.
+    fn control(&mut self) -> Atom {
+        let name = self.command().unwrap_or_default();
+
+        if let Some(symbol) = identifier_symbol(&name) {
+            return Atom::new(mi(symbol));
+        }
+        if let Some(symbol) = upright_identifier_symbol(&name) {
+            return Atom::new(format!("<mi mathvariant=\"normal\">{}</mi>", symbol));
+        }
+        if let Some(symbol) = operator_symbol(&name) {
+            return Atom::new(mo(symbol));
+        }
+        if let Some(symbol) = big_operator(&name) {
+            let limits = !symbol.starts_with(['∫', '∬', '∭', '∮']);
+            let mathml = format!(
+                "<mo largeop=\"true\" movablelimits=\"true\">{}</mo>",
+                symbol
+            );
+            return Atom { mathml, limits };
+        }
+        if FUNCTIONS.contains(&name.as_str()) {
+            return Atom::new(mi(&name));
+        }
+        if let Some(text) = limit_function(&name) {
+            return Atom::limits(mi(text));
+        }
+        if let Some(width) = space(&name) {
+            return Atom::new(format!("<mspace width=\"{}\" />", width));
+        }
+        if let Some(font) = font(&name) {
+            return Atom::new(self.with_font(font));
+        }
+        if let Some((accent, stretchy)) = accent(&name) {
+            let base = self.arg();
+            return Atom::new(format!(
+                "<mover accent=\"true\">{}<mo stretchy=\"{}\">{}</mo></mover>",
+                base, stretchy, accent
+            ));
+        }
+
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let top = self.arg();
+                let bottom = self.arg();
+                Atom::new(format!("<mfrac>{}{}</mfrac>", top, bottom))
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let top = self.arg();
+                let bottom = self.arg();
+                Atom::new(format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>",
+                    top, bottom
+                ))
+            }
+            "sqrt" => match self.bracketed() {
+                Some(index) => {
+                    let index = self.sub_expression(&index);
+                    let base = self.arg();
+                    Atom::new(format!("<mroot>{}{}</mroot>", base, index))
+                }
+                None => Atom::new(format!("<msqrt>{}</msqrt>", self.arg())),
+            },
+            "left" => {
+                let open = self.delimiter();
+                let mut inner = self.row().concat();
+                while self.eat_command("middle") {
+                    inner += &fence(&self.delimiter());
+                    inner += &self.row().concat();
+                }
+                let close = if self.eat_command("right") {
+                    self.delimiter()
+                } else {
+                    String::new()
+                };
+                Atom::new(format!(
+                    "<mrow>{}{}{}</mrow>",
+                    fence(&open),
+                    inner,
+                    fence(&close)
+                ))
+            }
+            "big" | "bigl" | "bigr" | "bigm" | "Big" | "Bigl" | "Bigr" | "Bigm" | "bigg"
+            | "biggl" | "biggr" | "biggm" | "Bigg" | "Biggl" | "Biggr" | "Biggm" => {
+                let size = match name.trim_end_matches(['l', 'r', 'm']) {
+                    "big" => "1.2em",
+                    "Big" => "1.8em",
+                    "bigg" => "2.4em",
+                    _ => "3em",
+                };
+                let delimiter = self.delimiter();
+                Atom::new(format!(
+                    "<mo stretchy=\"true\" symmetric=\"true\" minsize=\"{0}\" maxsize=\"{0}\">{1}</mo>",
+                    size,
+                    escape(&delimiter)
+                ))
+            }
+            "text" | "textrm" | "textnormal" | "mbox" | "textup" | "hbox" => {
+                Atom::new(text(&self.raw_arg(), None))
+            }
+            "textbf" => Atom::new(text(&self.raw_arg(), Some("bold"))),
+            "textit" | "emph" => Atom::new(text(&self.raw_arg(), Some("italic"))),
+            "texttt" => Atom::new(text(&self.raw_arg(), Some("monospace"))),
+            "textsf" => Atom::new(text(&self.raw_arg(), Some("sans-serif"))),
+            "operatorname" => {
+                let limits = self.eat('*');
+                let name = self.raw_arg();
+                let name = mi(name.trim());
+                Atom {
+                    mathml: name,
+                    limits,
+                }
+            }
+            "mathop" => Atom::limits(self.arg()),
+            "mathrel" | "mathbin" | "mathord" | "mathopen" | "mathclose" | "mathpunct" => {
+                Atom::new(self.arg())
+            }
+            // everything is display style already
+            "displaystyle" | "textstyle" | "scriptstyle" | "scriptscriptstyle" => {
+                Atom::new(String::new())
+            }
+            "textcolor" => {
+                let color = self.raw_arg();
+                let inner = self.arg();
+                Atom::new(format!(
+                    "<mstyle mathcolor=\"{}\">{}</mstyle>",
+                    escape(&color),
+                    inner
+                ))
+            }
+            "boxed" => Atom::new(format!(
+                "<mrow style=\"border:1px solid;padding:0.2em\">{}</mrow>",
+                self.arg()
+            )),
+            "phantom" => Atom::new(format!("<mphantom>{}</mphantom>", self.arg())),
+            "not" => {
+                let negated = self.atom().mathml;
+                // a slash through whatever comes next
+                Atom::new(match negated.strip_suffix("</mo>") {
+                    Some(negated) => format!("{}\u{338}</mo>", negated),
+                    None => format!("<mrow>{}{}</mrow>", negated, mo("\u{338}")),
+                })
+            }
+            "overset" | "stackrel" => {
+                let over = self.arg();
+                let base = self.arg();
+                Atom::new(format!("<mover>{}{}</mover>", base, over))
+            }
+            "underset" => {
+                let under = self.arg();
+                let base = self.arg();
+                Atom::new(format!("<munder>{}{}</munder>", base, under))
+            }
+            "overbrace" => Atom::limits(format!(
+                "<mover>{}<mo stretchy=\"true\">⏞</mo></mover>",
+                self.arg()
+            )),
+            "underbrace" => Atom::limits(format!(
+                "<munder>{}<mo stretchy=\"true\">⏟</mo></munder>",
+                self.arg()
+            )),
+            "underline" => Atom::new(format!(
+                "<munder accentunder=\"true\">{}<mo stretchy=\"true\">_</mo></munder>",
+                self.arg()
+            )),
+            "bmod" => Atom::new("<mo lspace=\"0.2222em\" rspace=\"0.2222em\">mod</mo>".to_string()),
+            "pmod" => Atom::new(format!(
+                "<mrow><mspace width=\"1em\" /><mo>(</mo><mi>mod</mi><mspace width=\"0.3333em\" />{}<mo>)</mo></mrow>",
+                self.arg()
+            )),
+            "begin" => {
+                let env = self.raw_arg();
+                Atom::new(self.environment(&env))
+            }
+            // escaped characters
+            "{" | "}" | "%" | "$" | "#" | "&" | "_" => Atom::new(mo(&name)),
+            _ => Atom::new(merror(&format!("\\{}", name))),
+        }
+    }
+
+    /// A delimiter for `\left`, `\right` and `\big`, `.` being none.
+    fn delimiter(&mut self) -> String {
+        if self.peek() == Some('\\') {
+            let name = self.command().unwrap_or_default();
+            return match name.as_str() {
+                "{" | "lbrace" => "{",
+                "}" | "rbrace" => "}",
+                "|" | "Vert" | "lVert" | "rVert" => "‖",
+                "vert" | "lvert" | "rvert" => "|",
+                "langle" => "⟨",
+                "rangle" => "⟩",
+                "lceil" => "⌈",
+                "rceil" => "⌉",
+                "lfloor" => "⌊",
+                "rfloor" => "⌋",
+                "lbrack" => "[",
+                "rbrack" => "]",
+                "backslash" => "∖",
+                "uparrow" => "↑",
+                "downarrow" => "↓",
+                _ => "",
+            }
+            .to_string();
+        }
+        match self.peek() {
+            Some('.') | None => {
+                self.pos += 1;
+                String::new()
+            }
+            Some(c) => {
+                self.pos += 1;
+                match c {
+                    '<' => "⟨".to_string(),
+                    '>' => "⟩".to_string(),
+                    c => c.to_string(),
+                }
+            }
+        }
+    }
+
+    /// The contents of `\begin{env}` up to and including its `\end`.
+    fn environment(&mut self, env: &str) -> String {
+        let name = env.trim_end_matches('*');
+        let columns = if name == "array" || name == "alignat" || name == "alignedat" {
+            self.raw_arg()
+        } else {
+            String::new()
+        };
+
+        let rows = self.table();
+        if self.eat_command("end") {
+            self.raw_arg();
+        }
+
+        let align: Vec<Align> = match name {
+            "array" => columns
+                .chars()
+                .filter_map(|c| match c {
+                    'l' => Some(Align::Left),
+                    'c' => Some(Align::Center),
+                    'r' => Some(Align::Right),
+                    _ => None,
+                })
+                .collect(),
+            "cases" | "rcases" | "dcases" => vec![Align::Left],
+            "aligned" | "align" | "alignat" | "alignedat" | "split" | "eqnarray" => {
+                vec![Align::Right, Align::Left]
+            }
+            _ => vec![Align::Center],
+        };
+        let rows = if align.len() == 2 {
+            // the right hand side of `a &= b` starts with an operator, which
+            // needs something before it to get spaced like one
+            rows.into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .enumerate()
+                        .map(|(i, cell)| {
+                            if i % 2 == 1 {
+                                format!("<mi></mi>{}", cell)
+                            } else {
+                                cell
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        } else {
+            rows
+        };
+        let table = mtable(rows, &align);
+
+        let (open, close) = match name {
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" => ("{", "}"),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("‖", "‖"),
+            "cases" | "dcases" => ("{", ""),
+            "rcases" => ("", "}"),
+            _ => return table,
+        };
+        format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close))
+    }
+}
+
+fn mi(text: &str) -> String {
+    format!("<mi>{}</mi>", escape(text))
+}
+
+fn mo(text: &str) -> String {
+    format!("<mo>{}</mo>", escape(text))
+}
+
+fn merror(text: &str) -> String {
+    format!("<merror><mtext>{}</mtext></merror>", escape(text))
+}
+
+/// A stretchy delimiter, or nothing for `\left.`.
+fn fence(delimiter: &str) -> String {
+    if delimiter.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<mo fence=\"true\" stretchy=\"true\">{}</mo>",
+        escape(delimiter)
+    )
+}
+
+fn text(text: &str, variant: Option<&str>) -> String {
+    // spaces at the ends of an <mtext> get dropped otherwise
+    let text = text.replace(' ', "\u{a0}").replace('\\', "");
+    match variant {
+        Some(variant) => format!(
+            "<mtext mathvariant=\"{}\">{}</mtext>",
+            variant,
+            escape(&text)
+        ),
+        None => format!("<mtext>{}</mtext>", escape(&text)),
+    }
+}
+
+fn mtable(rows: Vec<Vec<String>>, align: &[Align]) -> String {
+    let mut out = String::from("<mtable>");
+    for row in rows {
+        out += "<mtr>";
+        for (i, cell) in row.into_iter().enumerate() {
+            // an `array` with no usable column spec has no alignments at all
+            let align = align.get(i % align.len().max(1)).unwrap_or(&Align::Center);
+            let style = match align {
+                Align::Left => " style=\"text-align:left\" columnalign=\"left\"",
+                Align::Center => "",
+                Align::Right => " style=\"text-align:right\" columnalign=\"right\"",
+            };
+            out += &format!("<mtd{}>{}</mtd>", style, cell);
+        }
+        out += "</mtr>";
+    }
+    out + "</mtable>"
+}
+
+/// `c` in `font`, using the Mathematical Alphanumeric Symbols block since
+/// browsers mostly ignore `mathvariant`.
+fn styled(c: char, font: Option<Font>) -> char {
+    let Some(font) = font else {
+        return c;
+    };
+
+    let exception = match (font, c) {
+        (Font::Script, 'B') => Some('ℬ'),
+        (Font::Script, 'E') => Some('ℰ'),
+        (Font::Script, 'F') => Some('ℱ'),
+        (Font::Script, 'H') => Some('ℋ'),
+        (Font::Script, 'I') => Some('ℐ'),
+        (Font::Script, 'L') => Some('ℒ'),
+        (Font::Script, 'M') => Some('ℳ'),
+        (Font::Script, 'R') => Some('ℛ'),
+        (Font::Script, 'e') => Some('ℯ'),
+        (Font::Script, 'g') => Some('ℊ'),
+        (Font::Script, 'o') => Some('ℴ'),
+        (Font::Fraktur, 'C') => Some('ℭ'),
+        (Font::Fraktur, 'H') => Some('ℌ'),
+        (Font::Fraktur, 'I') => Some('ℑ'),
+        (Font::Fraktur, 'R') => Some('ℜ'),
+        (Font::Fraktur, 'Z') => Some('ℨ'),
+        (Font::DoubleStruck, 'C') => Some('ℂ'),
+        (Font::DoubleStruck, 'H') => Some('ℍ'),
+        (Font::DoubleStruck, 'N') => Some('ℕ'),
+        (Font::DoubleStruck, 'P') => Some('ℙ'),
+        (Font::DoubleStruck, 'Q') => Some('ℚ'),
+        (Font::DoubleStruck, 'R') => Some('ℝ'),
+        (Font::DoubleStruck, 'Z') => Some('ℤ'),
+        _ => None,
+    };
+    if let Some(c) = exception {
+        return c;
+    }
+
+    let (letters, digits) = match font {
+        Font::Upright => return c,
+        Font::Bold => (0x1D400, Some(0x1D7CE)),
+        Font::BoldItalic => (0x1D468, Some(0x1D7CE)),
+        Font::Script => (0x1D49C, None),
+        Font::Fraktur => (0x1D504, None),
+        Font::DoubleStruck => (0x1D538, Some(0x1D7D8)),
+        Font::SansSerif => (0x1D5A0, Some(0x1D7E2)),
+        Font::Monospace => (0x1D670, Some(0x1D7F6)),
+    };
+    let code = match c {
+        'A'..='Z' => letters + (c as u32 - 'A' as u32),
+        'a'..='z' => letters + 26 + (c as u32 - 'a' as u32),
+        '0'..='9' => match digits {
+            Some(digits) => digits + (c as u32 - '0' as u32),
+            None => return c,
+        },
+        _ => return c,
+    };
+    char::from_u32(code).unwrap_or(c)
+}
+
+fn font(name: &str) -> Option<Option<Font>> {
+    Some(Some(match name {
+        "mathrm" | "rm" | "mathup" => Font::Upright,
+        "mathbf" | "bf" => Font::Bold,
+        "boldsymbol" | "bm" => Font::BoldItalic,
+        "mathcal" | "mathscr" | "cal" => Font::Script,
+        "mathfrak" | "frak" => Font::Fraktur,
+        "mathbb" | "Bbb" => Font::DoubleStruck,
+        "mathsf" | "sf" => Font::SansSerif,
+        "mathtt" | "tt" => Font::Monospace,
+        "mathit" | "mathnormal" | "it" => return Some(None),
+        _ => return None,
+    }))
+}
+
+/// Greek letters and other symbols that are variables rather than
+/// operators, and so italic like any other variable.
+fn identifier_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "varkappa" => "ϰ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "omicron" => "ο",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "ell" => "ℓ",
+        "imath" => "ı",
+        "jmath" => "ȷ",
+        "wp" => "℘",
+        _ => return None,
+    })
+}
+
+/// Symbols that are variables but stay upright, like capital Greek letters.
+fn upright_identifier_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "emptyset" | "varnothing" => "∅",
+        "aleph" => "ℵ",
+        "hbar" | "hslash" => "ℏ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "angle" => "∠",
+        "triangle" => "△",
+        "top" => "⊤",
+        "bot" => "⊥",
+        "dagger" => "†",
+        "ddagger" => "‡",
+        "checkmark" => "✓",
+        "S" => "§",
+        _ => return None,
+    })
+}
+
+fn operator_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "pm" => "±",
+        "mp" => "∓",
+        "times" => "×",
+        "div" => "÷",
+        "cdot" => "⋅",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "oplus" => "⊕",
+        "ominus" => "⊖",
+        "otimes" => "⊗",
+        "oslash" => "⊘",
+        "odot" => "⊙",
+        "cup" => "∪",
+        "cap" => "∩",
+        "sqcup" => "⊔",
+        "sqcap" => "⊓",
+        "uplus" => "⊎",
+        "setminus" | "backslash" => "∖",
+        "wedge" | "land" => "∧",
+        "vee" | "lor" => "∨",
+        "neg" | "lnot" => "¬",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "leqslant" => "⩽",
+        "geqslant" => "⩾",
+        "neq" | "ne" => "≠",
+        "ll" => "≪",
+        "gg" => "≫",
+        "lt" => "<",
+        "gt" => ">",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "doteq" => "≐",
+        "prec" => "≺",
+        "succ" => "≻",
+        "preceq" => "⪯",
+        "succeq" => "⪰",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" | "owns" => "∋",
+        "subset" => "⊂",
+        "supset" => "⊃",
+        "subseteq" => "⊆",
+        "supseteq" => "⊇",
+        "subsetneq" => "⊊",
+        "supsetneq" => "⊋",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" => "⇔",
+        "longrightarrow" => "⟶",
+        "longleftarrow" => "⟵",
+        "longleftrightarrow" => "⟷",
+        "Longrightarrow" | "implies" => "⟹",
+        "Longleftarrow" | "impliedby" => "⟸",
+        "Longleftrightarrow" | "iff" => "⟺",
+        "mapsto" => "↦",
+        "longmapsto" => "⟼",
+        "hookrightarrow" => "↪",
+        "hookleftarrow" => "↩",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "updownarrow" => "↕",
+        "Uparrow" => "⇑",
+        "Downarrow" => "⇓",
+        "nearrow" => "↗",
+        "searrow" => "↘",
+        "nwarrow" => "↖",
+        "swarrow" => "↙",
+        "rightleftharpoons" => "⇌",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" => "∣",
+        "nmid" => "∤",
+        "vert" | "lvert" | "rvert" => "|",
+        "|" | "Vert" | "lVert" | "rVert" => "‖",
+        "ldots" | "dots" | "dotsc" | "dotso" => "…",
+        "cdots" | "dotsb" | "dotsm" | "dotsi" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "colon" => ":",
+        "prime" => "′",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lbrace" => "{",
+        "rbrace" => "}",
+        "lbrack" => "[",
+        "rbrack" => "]",
+        "therefore" => "∴",
+        "because" => "∵",
+        "models" => "⊨",
+        "vdash" => "⊢",
+        "dashv" => "⊣",
+        "wr" => "≀",
+        "diamond" => "⋄",
+        "triangleleft" => "◁",
+        "triangleright" => "▷",
+        "bigtriangleup" => "△",
+        "bigtriangledown" => "▽",
+        "bigcirc" => "◯",
+        "cdotp" => "⋅",
+        "ldotp" => ".",
+        _ => return None,
+    })
+}
+
+fn big_operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "bigsqcup" => "⨆",
+        "bigvee" => "⋁",
+        "bigwedge" => "⋀",
+        "bigoplus" => "⨁",
+        "bigotimes" => "⨂",
+        "bigodot" => "⨀",
+        "biguplus" => "⨄",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        _ => return None,
+    })
+}
+
+/// Functions set upright, with scripts to the side.
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "coth", "log", "ln", "lg", "exp", "ker", "dim", "deg", "arg", "hom",
+];
+
+/// Functions whose subscripts go underneath, like `\lim_{x \to 0}`.
+fn limit_function(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "lim" => "lim",
+        "limsup" => "lim sup",
+        "liminf" => "lim inf",
+        "max" => "max",
+        "min" => "min",
+        "sup" => "sup",
+        "inf" => "inf",
+        "det" => "det",
+        "gcd" => "gcd",
+        "Pr" => "Pr",
+        "argmax" => "arg max",
+        "argmin" => "arg min",
+        _ => return None,
+    })
+}
+
+fn space(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "," | "thinspace" => "0.1667em",
+        ":" | ">" | "medspace" => "0.2222em",
+        ";" | "thickspace" => "0.2778em",
+        "!" | "negthinspace" => "-0.1667em",
+        " " | "space" => "0.25em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        _ => return None,
+    })
+}
+
+/// The mark an accent command puts over its argument, and whether it
+/// stretches to cover all of it.
+fn accent(name: &str) -> Option<(&'static str, bool)> {
+    Some(match name {
+        "hat" => ("^", false),
+        "widehat" => ("^", true),
+        "check" => ("ˇ", false),
+        "tilde" => ("~", false),
+        "widetilde" => ("~", true),
+        "acute" => ("´", false),
+        "grave" => ("`", false),
+        "dot" => ("˙", false),
+        "ddot" => ("¨", false),
+        "breve" => ("˘", false),
+        "bar" => ("¯", false),
+        "overline" => ("¯", true),
+        "vec" => ("→", false),
+        "overrightarrow" => ("→", true),
+        "overleftarrow" => ("←", true),
+        "overleftrightarrow" => ("↔", true),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_mathml;
+
+    /// The MathML inside `<semantics>`, without the annotation.
+    fn body(tex: &str) -> String {
+        let out = to_mathml(tex);
+        let start = out.find("<semantics>").unwrap() + "<semantics>".len();
+        let end = out.find("<annotation").unwrap();
+        out[start..end].to_string()
+    }
+
+    #[test]
+    fn array_without_column_spec() {
+        for tex in [
+            "\\begin{array}{}a\\end{array}",
+            "\\begin{array}{|}a & b\\end{array}",
+            "\\begin{array}",
+        ] {
+            assert!(body(tex).contains("<mtable>"), "{}", tex);
+        }
+    }
+
+    #[test]
+    fn stray_closers() {
+        assert_eq!(
+            body("a } b"),
+            "<mrow><mi>a</mi><merror><mtext>}</mtext></merror><mi>b</mi></mrow>"
+        );
+        assert_eq!(
+            body("a \\right) b"),
+            "<mrow><mi>a</mi><merror><mtext>\\right</mtext></merror><mi>b</mi></mrow>"
+        );
+    }
+
+    #[test]
+    fn unclosed_left() {
+        assert_eq!(
+            body("\\left( x"),
+            "<mrow><mrow><mo fence=\"true\" stretchy=\"true\">(</mo><mi>x</mi></mrow></mrow>"
+        );
+    }
+
+    #[test]
+    fn unclosed_root_index() {
+        assert_eq!(
+            body("\\sqrt["),
+            "<mrow><mroot><mrow></mrow><mrow></mrow></mroot></mrow>"
+        );
+        assert_eq!(
+            body("\\sqrt[3"),
+            "<mrow><mroot><mrow></mrow><mrow><mn>3</mn></mrow></mroot></mrow>"
+        );
+    }
+
+    #[test]
+    fn fraction_with_scripts() {
+        assert_eq!(
+            body("\\frac{a}{b^2}"),
+            "<mrow><mfrac><mrow><mi>a</mi></mrow><mrow><msup><mi>b</mi><mn>2</mn></msup></mrow></mfrac></mrow>"
+        );
+    }
+}
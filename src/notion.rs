@@ -1,19 +1,24 @@
 use std::fmt::Display;
 
-use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use log::warn;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
 
 #[derive(Debug, Deserialize)]
 pub struct PageResponse {
     //pub object: String,
     pub results: Vec<Block>,
-    //pub next_cursor: Option<String>,
-    //pub has_more: bool,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(from = "RawBlock")]
 pub struct Block {
     //pub object: String,
-    //pub id: String,
+    pub id: String,
     //parent: Parent,
     //#[serde(rename = "created_time")]
     //pub created: String,
@@ -21,32 +26,118 @@ pub struct Block {
     //pub last_edited: String,
     // created_by: {object, id}
     // last_edited_by: {object, id}
-    //pub has_children: bool,
+    pub has_children: bool,
     //pub archived: bool,
     //pub in_trash: bool,
-    #[serde(rename = "type")]
     pub ty: String,
-    #[serde(flatten)]
     pub block: BlockData,
 }
 
+/// A block as it comes over the wire, before its type-specific data has been
+/// picked apart. Going through this lets a block we can't make sense of turn
+/// into [`BlockData::Unknown`] instead of failing the whole response.
+#[derive(Deserialize)]
+struct RawBlock {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    has_children: bool,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(flatten)]
+    rest: Map<String, Value>,
+}
+
+impl From<RawBlock> for Block {
+    fn from(mut raw: RawBlock) -> Self {
+        let data = raw.rest.remove(&raw.ty).unwrap_or(Value::Null);
+
+        let block = if BLOCK_TYPES.contains(&raw.ty.as_str()) {
+            let mut tagged = Map::new();
+            tagged.insert(raw.ty.clone(), data.clone());
+
+            match serde_json::from_value(Value::Object(tagged)) {
+                Ok(block) => block,
+                Err(err) => {
+                    warn!("Couldn't read {} block {}: {}", raw.ty, raw.id, err);
+                    BlockData::Unknown(data)
+                }
+            }
+        } else {
+            warn!("Unknown block type {} for block {}", raw.ty, raw.id);
+            BlockData::Unknown(data)
+        };
+
+        Block {
+            id: raw.id,
+            has_children: raw.has_children,
+            ty: raw.ty,
+            block,
+        }
+    }
+}
+
+/// The `type`s [`BlockData`] has a variant for. Keep in sync with it.
+const BLOCK_TYPES: &[&str] = &[
+    "audio",
+    "bookmark",
+    "breadcrumb",
+    "bulleted_list_item",
+    "callout",
+    "child_database",
+    "child_page",
+    "code",
+    "column_list",
+    "column",
+    "divider",
+    "embed",
+    "equation",
+    "file",
+    "heading_1",
+    "heading_2",
+    "heading_3",
+    "image",
+    "link_preview",
+    "mention",
+    "numbered_list_item",
+    "paragraph",
+    "pdf",
+    "quote",
+    "synced_block",
+    "table",
+    "table_row",
+    "table_of_contents",
+    "to_do",
+    "toggle",
+    "video",
+];
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BlockData {
+    Audio {
+        #[serde(default)]
+        caption: Vec<RichText>,
+        name: Option<String>,
+        #[serde(flatten)]
+        source: FileObject,
+    },
     Bookmark {
-        //caption: Vec<RichText>,
-        //url: String,
+        #[serde(default)]
+        caption: Vec<RichText>,
+        url: String,
     },
-    Breadcrumb,
+    Breadcrumb {},
     BulletedListItem {
         rich_text: Vec<RichText>,
         //color: NotionColor,
         children: Option<Vec<Block>>,
     },
     Callout {
-        //rich_text: Vec<RichText>,
-        // icon (emoji or file)
-        //color: NotionColor,
+        rich_text: Vec<RichText>,
+        icon: Option<Icon>,
+        color: NotionColor,
+        children: Option<Vec<Block>>,
     },
     ChildDatabase {
         //title: String,
@@ -59,75 +150,106 @@ pub enum BlockData {
         rich_text: Vec<RichText>,
         language: NotionLanguages,
     },
-    ColumnList,
-    Column,
-    Divider,
+    ColumnList {
+        children: Option<Vec<Block>>,
+    },
+    Column {
+        /// This column's share of the list's width, only sent by newer
+        /// versions of the API.
+        width_ratio: Option<f64>,
+        children: Option<Vec<Block>>,
+    },
+    Divider {},
     Embed {
-        //url: String,
+        #[serde(default)]
+        caption: Vec<RichText>,
+        url: String,
     },
     Equation {
-        //expression: String,
+        /// KaTeX-flavoured LaTeX, without delimiters.
+        expression: String,
     },
     File {
-        // TODO: file
-        //caption: Vec<RichText>,
-        //#[serde(rename = "type")]
-        //ty: String,
-        //name: String,
+        #[serde(default)]
+        caption: Vec<RichText>,
+        /// The file's name, which Notion only sends for uploaded files.
+        name: Option<String>,
+        #[serde(flatten)]
+        source: FileObject,
     },
     #[serde(rename = "heading_1")]
     Heading1 {
         rich_text: Vec<RichText>,
         //color: NotionColor,
-        //is_toggleable: bool,
+        #[serde(default)]
+        is_toggleable: bool,
+        children: Option<Vec<Block>>,
     },
     #[serde(rename = "heading_2")]
     Heading2 {
         rich_text: Vec<RichText>,
         //color: NotionColor,
-        //is_toggleable: bool,
+        #[serde(default)]
+        is_toggleable: bool,
+        children: Option<Vec<Block>>,
     },
     #[serde(rename = "heading_3")]
     Heading3 {
         rich_text: Vec<RichText>,
         //color: NotionColor,
-        //is_toggleable: bool,
+        #[serde(default)]
+        is_toggleable: bool,
+        children: Option<Vec<Block>>,
     },
     Image {
-        //#[serde(rename = "type")]
-        //ty: String,
-        // TODO: image
+        #[serde(default)]
+        caption: Vec<RichText>,
+        #[serde(flatten)]
+        source: FileObject,
     },
+    /// A link Notion shows a live preview of, like a GitHub issue or Slack
+    /// message.
     LinkPreview {
-        //url: String,
+        url: String,
     },
     Mention(/* MentionData */),
     NumberedListItem {
         rich_text: Vec<RichText>,
         //color: NotionColor,
+        /// Set when the user restarted numbering at this item.
+        list_start_index: Option<usize>,
         children: Option<Vec<Block>>,
     },
     Paragraph {
         rich_text: Vec<RichText>,
         //color: NotionColor,
-        //children: Option<Vec<Block>>,
+        children: Option<Vec<Block>>,
     },
     Pdf {
-        // TODO: pdf
+        #[serde(default)]
+        caption: Vec<RichText>,
+        name: Option<String>,
+        #[serde(flatten)]
+        source: FileObject,
     },
     Quote {
         rich_text: Vec<RichText>,
         //color: NotionColor,
         children: Option<Vec<Block>>,
     },
-    // synced block
+    SyncedBlock {
+        //synced_from: Option<{type, block_id}>,
+        children: Option<Vec<Block>>,
+    },
     Table {
-        //table_width: usize,
-        //has_column_header: bool,
+        table_width: usize,
+        has_column_header: bool,
+        has_row_header: bool,
         //has_column_totals: bool,
+        children: Option<Vec<Block>>,
     },
     TableRow {
-        //cells: Vec<RichText>,
+        cells: Vec<Vec<RichText>>,
     },
     TableOfContents {
         //color: NotionColor,
@@ -139,13 +261,171 @@ pub enum BlockData {
         children: Option<Vec<Block>>,
     },
     Toggle {
-        //rich_text: Vec<RichText>,
+        rich_text: Vec<RichText>,
         //color: NotionColor,
-        //children: Option<Vec<Block>>,
+        children: Option<Vec<Block>>,
     },
     Video {
-        // TODO: file
+        #[serde(default)]
+        caption: Vec<RichText>,
+        name: Option<String>,
+        #[serde(flatten)]
+        source: FileObject,
     },
+    /// Any block type we don't know about (or couldn't parse), holding the raw
+    /// JSON under its type key.
+    #[serde(skip)]
+    Unknown(Value),
+}
+
+impl BlockData {
+    /// Where the children of this block live, if it can have any.
+    pub fn children_mut(&mut self) -> Option<&mut Option<Vec<Block>>> {
+        match self {
+            BlockData::BulletedListItem { children, .. }
+            | BlockData::Callout { children, .. }
+            | BlockData::ColumnList { children }
+            | BlockData::Column { children, .. }
+            | BlockData::Heading1 { children, .. }
+            | BlockData::Heading2 { children, .. }
+            | BlockData::Heading3 { children, .. }
+            | BlockData::NumberedListItem { children, .. }
+            | BlockData::Paragraph { children, .. }
+            | BlockData::Quote { children, .. }
+            | BlockData::SyncedBlock { children }
+            | BlockData::Table { children, .. }
+            | BlockData::ToDo { children, .. }
+            | BlockData::Toggle { children, .. } => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// A page object, from `GET /v1/pages/{id}`.
+#[derive(Debug, Deserialize)]
+pub struct Page {
+    //pub object: String,
+    pub id: String,
+    pub created_time: String,
+    pub last_edited_time: String,
+    pub icon: Option<Icon>,
+    pub cover: Option<FileObject>,
+    #[serde(default, deserialize_with = "lenient_properties")]
+    pub properties: BTreeMap<String, Property>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Icon {
+    Emoji {
+        emoji: String,
+    },
+    External {
+        external: Url,
+    },
+    File {
+        file: Url,
+    },
+    // custom emoji, etc.
+    #[serde(other)]
+    Unsupported,
+}
+
+/// A file Notion hosts (whose URL expires after an hour) or a link to one
+/// somewhere else.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FileObject {
+    External { external: Url },
+    File { file: Url },
+}
+
+impl FileObject {
+    pub fn url(&self) -> &str {
+        match self {
+            FileObject::External { external } => &external.url,
+            FileObject::File { file } => &file.url,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Property {
+    Title {
+        title: Vec<RichText>,
+    },
+    RichText {
+        rich_text: Vec<RichText>,
+    },
+    Number {
+        number: Option<f64>,
+    },
+    Select {
+        select: Option<SelectOption>,
+    },
+    Status {
+        status: Option<SelectOption>,
+    },
+    MultiSelect {
+        multi_select: Vec<SelectOption>,
+    },
+    Date {
+        date: Option<DateRange>,
+    },
+    Checkbox {
+        checkbox: bool,
+    },
+    Url {
+        url: Option<String>,
+    },
+    Email {
+        email: Option<String>,
+    },
+    PhoneNumber {
+        phone_number: Option<String>,
+    },
+    CreatedTime {
+        created_time: String,
+    },
+    LastEditedTime {
+        last_edited_time: String,
+    },
+    // people, files, relations, rollups, formulas, ...
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SelectOption {
+    pub name: String,
+    //pub color: NotionColor,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DateRange {
+    pub start: String,
+    pub end: Option<String>,
+}
+
+/// Like [`Block`], a property we can't read shouldn't take the whole page
+/// down with it.
+fn lenient_properties<'de, D>(deserializer: D) -> Result<BTreeMap<String, Property>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = BTreeMap::<String, Value>::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .map(|(name, value)| {
+            let property = serde_json::from_value(value).unwrap_or_else(|err| {
+                warn!("Couldn't read property {:?}: {}", name, err);
+                Property::Unsupported
+            });
+            (name, property)
+        })
+        .collect())
 }
 
 #[derive(Debug, Deserialize)]
@@ -278,10 +558,10 @@ impl Display for NotionLanguages {
     }
 }
 
-/*#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Url {
     pub url: String,
-}*/
+}
 
 /*#[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -334,3 +614,45 @@ pub enum NotionColor {
     Red,
     RedBackground,
 }
+
+impl NotionColor {
+    pub fn is_background(&self) -> bool {
+        matches!(
+            self,
+            NotionColor::BlueBackground
+                | NotionColor::BrownBackground
+                | NotionColor::GrayBackground
+                | NotionColor::GreenBackground
+                | NotionColor::OrangeBackground
+                | NotionColor::YellowBackground
+                | NotionColor::PinkBackground
+                | NotionColor::PurpleBackground
+                | NotionColor::RedBackground
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, BlockData, BLOCK_TYPES};
+    use serde_json::json;
+
+    #[test]
+    fn unknown_block_types_are_kept() {
+        let block: Block =
+            serde_json::from_value(json!({"type": "wibble", "wibble": {"a": 1}})).unwrap();
+        assert!(matches!(block.block, BlockData::Unknown(data) if data == json!({"a": 1})));
+    }
+
+    #[test]
+    fn every_known_type_is_a_variant() {
+        // an empty object is never a valid block, but serde only complains
+        // about a missing field (rather than the variant) for types it knows
+        for ty in BLOCK_TYPES {
+            let err = serde_json::from_value::<BlockData>(json!({ *ty: {} }));
+            if let Err(err) = err {
+                assert!(!err.to_string().contains("unknown variant"), "{}", ty);
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use reqwest::{
+    blocking::{Client, ClientBuilder, RequestBuilder, Response},
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A reqwest client that retries rate limits, gateway errors and dropped
+/// connections instead of giving up on the first failure.
+pub struct RetryClient {
+    client: Client,
+    max_attempts: u32,
+    /// Xorshift state for jitter. Atomic so a client can be shared between
+    /// threads.
+    rng: AtomicU64,
+}
+
+impl RetryClient {
+    pub fn new(client: Client) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Self {
+            client,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            // xorshift gets stuck on zero
+            rng: AtomicU64::new(seed | 1),
+        }
+    }
+
+    /// A client with the settings every request we make uses, like our
+    /// user agent.
+    pub fn with_default_client() -> reqwest::Result<Self> {
+        let client = ClientBuilder::new()
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION"),
+            ))
+            .build()?;
+        Ok(Self::new(client))
+    }
+
+    /// How many times a request is tried in total before the last failure is
+    /// handed back. Anything below 1 is treated as 1.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    /// Sends the request, retrying 429, 502, 503 and 504 responses as well as
+    /// connection errors and timeouts. Notion's `Retry-After` header is used
+    /// when present, otherwise the delay backs off exponentially with jitter.
+    pub fn send(&self, req: RequestBuilder) -> reqwest::Result<Response> {
+        let req = req.build()?;
+        let mut attempt = 1;
+
+        loop {
+            let last = attempt >= self.max_attempts;
+            let this = match req.try_clone() {
+                Some(this) if !last => this,
+                _ => return self.client.execute(req),
+            };
+
+            let delay = match self.client.execute(this) {
+                Ok(res) if should_retry(res.status()) => {
+                    let delay = retry_after(res.headers()).unwrap_or_else(|| self.backoff(attempt));
+                    warn!(
+                        "Got {} from {}, retrying in {:.1}s (attempt {}/{})",
+                        res.status(),
+                        req.url().path(),
+                        delay.as_secs_f32(),
+                        attempt,
+                        self.max_attempts
+                    );
+                    delay
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        "Request to {} failed ({}), retrying in {:.1}s (attempt {}/{})",
+                        req.url().path(),
+                        e,
+                        delay.as_secs_f32(),
+                        attempt,
+                        self.max_attempts
+                    );
+                    delay
+                }
+                res => return res,
+            };
+
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Exponential backoff with "equal jitter": half the delay is fixed, the
+    /// other half random, so concurrent retries spread out but never fire
+    /// immediately.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = BASE_DELAY
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_DELAY);
+        let half = exp / 2;
+        let jitter = self.next_random() % (half.as_millis() as u64 + 1);
+        half + Duration::from_millis(jitter)
+    }
+
+    fn next_random(&self) -> u64 {
+        let step = |mut x: u64| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let previous = self
+            .rng
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(step(x)))
+            .unwrap_or_else(|x| x);
+        step(previous)
+    }
+}
+
+fn should_retry(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Notion sends `Retry-After` as a number of seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    let secs: f64 = value.trim().parse().ok()?;
+    if !secs.is_finite() || secs < 0.0 {
+        debug!("Ignoring bogus Retry-After header {:?}", value);
+        return None;
+    }
+    Some(Duration::from_secs_f64(secs).min(Duration::from_secs(300)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::{
+        blocking::Client,
+        header::{HeaderMap, HeaderValue, RETRY_AFTER},
+        StatusCode,
+    };
+
+    use super::{retry_after, should_retry, RetryClient, BASE_DELAY, MAX_DELAY};
+
+    fn need_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn clients_can_be_shared_between_threads() {
+        need_send_sync::<RetryClient>();
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let parse = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            retry_after(&headers)
+        };
+        assert_eq!(parse("2"), Some(Duration::from_secs(2)));
+        assert_eq!(parse(" 0.5 "), Some(Duration::from_millis(500)));
+        assert_eq!(parse("100000"), Some(Duration::from_secs(300)));
+        assert_eq!(parse("-1"), None);
+        assert_eq!(parse("NaN"), None);
+        assert_eq!(parse("inf"), None);
+        assert_eq!(parse("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn backoff_stays_in_range() {
+        let client = RetryClient::new(Client::new());
+        for attempt in 1..=40 {
+            let exp = BASE_DELAY
+                .saturating_mul(2u32.saturating_pow(attempt - 1))
+                .min(MAX_DELAY);
+            for _ in 0..100 {
+                let delay = client.backoff(attempt);
+                assert!(exp / 2 <= delay && delay <= exp, "{:?} {:?}", delay, exp);
+                assert!(delay <= MAX_DELAY);
+            }
+        }
+    }
+
+    #[test]
+    fn retried_statuses() {
+        for code in 100..600 {
+            let status = StatusCode::from_u16(code).unwrap();
+            assert_eq!(
+                should_retry(status),
+                matches!(code, 429 | 502 | 503 | 504),
+                "{}",
+                code
+            );
+        }
+    }
+}